@@ -0,0 +1,51 @@
+use quicksilver::{
+  geom::{Rectangle, Vector},
+  graphics::{View},
+};
+
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.;
+
+/// Which part of the world is on screen, `pos` is the world position of the
+/// top left corner and `zoom` the number of screen pixels per world pixel
+pub struct Camera {
+  pub pos: Vector,
+  pub zoom: f32,
+}
+
+impl Camera {
+  pub fn new() -> Self {
+    Camera {
+      pos: Vector::ZERO,
+      zoom: 1.,
+    }
+  }
+
+  pub fn view(&self, screen_size: Vector) -> View {
+    View::new(self.visible(screen_size))
+  }
+
+  /// The world area that fits on a screen of the given size
+  pub fn visible(&self, screen_size: Vector) -> Rectangle {
+    Rectangle::new(self.pos, screen_size / self.zoom)
+  }
+
+  /// The world position under a screen position
+  pub fn to_world(&self, screen_pos: Vector) -> Vector {
+    self.pos + screen_pos / self.zoom
+  }
+
+  /// Moves by an amount of screen pixels
+  pub fn pan(&mut self, screen_delta: Vector) {
+    self.pos += screen_delta / self.zoom;
+  }
+
+  /// Zooms by `factor` keeping the world position `anchor` at the same place on screen
+  pub fn zoom_at(&mut self, anchor: Vector, factor: f32) {
+    let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    let screen_anchor = (anchor - self.pos) * self.zoom;
+
+    self.zoom = zoom;
+    self.pos = anchor - screen_anchor / zoom;
+  }
+}
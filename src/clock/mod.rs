@@ -0,0 +1,45 @@
+/// Length of one simulation tick in seconds
+pub const TICK: f32 = 1. / 60.;
+
+/// Turns real elapsed time into a whole number of fixed simulation ticks
+pub struct Clock {
+  tick: f32,
+  accumulator: f32,
+  max_ticks: u32,
+}
+
+impl Clock {
+  /// `max_ticks` caps how far the simulation catches up in one go,
+  /// anything beyond it is dropped so a long stall doesn't freeze the game
+  pub fn new(tick: f32, max_ticks: u32) -> Self {
+    Clock {
+      tick,
+      accumulator: 0.,
+      max_ticks,
+    }
+  }
+
+  pub fn tick(&self) -> f32 {
+    self.tick
+  }
+
+  /// Adds the elapsed time and returns how many ticks to simulate
+  pub fn advance(&mut self, elapsed: f32) -> u32 {
+    self.accumulator += elapsed.max(0.);
+
+    let ticks = (self.accumulator / self.tick).floor() as u32;
+    self.accumulator -= ticks as f32 * self.tick;
+
+    if ticks > self.max_ticks {
+      self.accumulator = 0.;
+      self.max_ticks
+    } else {
+      ticks
+    }
+  }
+
+  /// How far into the next tick we are, `since` is the time passed since the last `advance`
+  pub fn alpha(&self, since: f32) -> f32 {
+    ((self.accumulator + since.max(0.)) / self.tick).min(1.)
+  }
+}
@@ -0,0 +1,81 @@
+use quicksilver::{
+  geom::Rectangle,
+  graphics::Color,
+  lifecycle::Window,
+};
+
+// glyphs are 3 dots wide and 5 high, one more column of space between them
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const ADVANCE: f32 = 4.;
+
+// rows from the top, the highest bit of each row is its left dot
+fn glyph(c: char) -> u16 {
+  match c.to_ascii_uppercase() {
+    'A' => 0b010_101_111_101_101,
+    'B' => 0b110_101_110_101_110,
+    'C' => 0b011_100_100_100_011,
+    'D' => 0b110_101_101_101_110,
+    'E' => 0b111_100_110_100_111,
+    'F' => 0b111_100_110_100_100,
+    'G' => 0b011_100_101_101_011,
+    'H' => 0b101_101_111_101_101,
+    'I' => 0b111_010_010_010_111,
+    'J' => 0b001_001_001_101_010,
+    'K' => 0b101_101_110_101_101,
+    'L' => 0b100_100_100_100_111,
+    'M' => 0b101_111_111_101_101,
+    'N' => 0b110_101_101_101_101,
+    'O' => 0b010_101_101_101_010,
+    'P' => 0b110_101_110_100_100,
+    'Q' => 0b010_101_101_110_011,
+    'R' => 0b110_101_110_101_101,
+    'S' => 0b011_100_010_001_110,
+    'T' => 0b111_010_010_010_010,
+    'U' => 0b101_101_101_101_111,
+    'V' => 0b101_101_101_101_010,
+    'W' => 0b101_101_111_111_101,
+    'X' => 0b101_101_010_101_101,
+    'Y' => 0b101_101_010_010_010,
+    'Z' => 0b111_001_010_100_111,
+    '0' => 0b111_101_101_101_111,
+    '1' => 0b010_110_010_010_111,
+    '2' => 0b110_001_010_100_111,
+    '3' => 0b110_001_010_001_110,
+    '4' => 0b101_101_111_001_001,
+    '5' => 0b111_100_110_001_110,
+    '6' => 0b011_100_111_101_111,
+    '7' => 0b111_001_010_010_010,
+    '8' => 0b111_101_111_101_111,
+    '9' => 0b111_101_111_001_110,
+    '-' => 0b000_000_111_000_000,
+    '.' => 0b000_000_000_000_010,
+    ' ' => 0,
+    _ => 0b110_001_010_000_010,
+  }
+}
+
+/// Width of `text` drawn with dots `scale` pixels big
+pub fn text_width(text: &str, scale: f32) -> f32 {
+  let len = text.chars().count() as f32;
+  (len * ADVANCE - 1.).max(0.) * scale
+}
+
+/// Draws `text` with its top left corner at `pos`, there is no font so the
+/// letters are made of square dots `scale` pixels big
+pub fn draw_text(window: &mut Window, text: &str, (x, y): (f32, f32), scale: f32, color: Color) {
+  for (i, c) in text.chars().enumerate() {
+    let bits = glyph(c);
+    let left = x + i as f32 * ADVANCE * scale;
+
+    for row in 0..GLYPH_HEIGHT {
+      for col in 0..GLYPH_WIDTH {
+        let bit = (GLYPH_HEIGHT - 1 - row) * GLYPH_WIDTH + (GLYPH_WIDTH - 1 - col);
+
+        if bits & (1 << bit) != 0 {
+          window.draw(&Rectangle::new((left + col as f32 * scale, y + row as f32 * scale), (scale, scale)), color);
+        }
+      }
+    }
+  }
+}
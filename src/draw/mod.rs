@@ -0,0 +1,191 @@
+mod font;
+
+use quicksilver::{
+  geom::{Rectangle, Vector, Circle, Transform},
+  graphics::{Color},
+  lifecycle::{Window},
+};
+
+use quicksilver_train::{
+  path::{
+    track::{
+      Track,
+      TrackPiece,
+      TURN_DIVISIONS,
+    },
+    Path,
+  },
+  train::{
+    Colour,
+    Train,
+    TrainState,
+  },
+  world::World,
+};
+
+use self::font::{draw_text, text_width};
+
+// how far off the track signals get drawn
+const SIGNAL_OFFSET: f32 = 8.;
+// half the size of the cross marking a crash
+const CRASH_SIZE: f32 = 8.;
+// width of platforms
+const PLATFORM_WIDTH: f32 = 12.;
+// size of the dots station names are made of
+const NAME_SCALE: f32 = 2.;
+
+pub fn draw_line(window: &mut Window, x: f32, y: f32, ex: f32, ey: f32, width: f32, color: Color) {
+  let is_x = x != ex;
+  let diagonal = is_x && y != ey;
+
+  let dx = ex - x;
+  let dy = ey - y;
+
+  let len = if diagonal {
+    (dx.abs().powi(2) + dy.abs().powi(2)).sqrt()
+  } else if is_x {
+    (ex - x).abs()
+  } else {
+    (ey - y).abs()
+  };
+
+  let angle = if diagonal {
+    (180. * (dx / -dy).atan() / std::f32::consts::PI) - 90.
+  } else if is_x {
+    0.
+  } else {
+    90.
+  };
+
+  let center = Vector::new(x + dx / 2.0, y + dy / 2.0);
+  // drawing is top left so we have to offset
+  let half_width = width / 2.;
+  let off = Vector::new(-len / 2., -half_width);
+
+  window.draw_ex(
+    &Rectangle::new((0, 0), (len, width)),
+    color,
+    Transform::translate(center + off) * Transform::rotate(angle),
+    0.0
+  );
+}
+
+pub fn draw_track(window: &mut Window, track: &Track, color: Color) {
+  let mut points: Vec<(f32, f32)> = Vec::new();
+  points.push(track.start().pos.to_float());
+
+  if let Track::Turn(_) | Track::SCurve(_) = track {
+    for i in 1..TURN_DIVISIONS {
+      points.push(track.lerp(i as f32 / TURN_DIVISIONS as f32));
+    }
+  }
+
+  points.push(track.end().pos.to_float());
+
+  for win in points.windows(2) {
+    let (prev, curr) = (win[0], win[1]);
+    draw_line(window, prev.0, prev.1, curr.0, curr.1, 2., color);
+  }
+
+  // the diamond where the other track goes over
+  if let Track::Cross(crossing) = track {
+    let (x, y) = crossing.center().to_float();
+
+    window.draw_ex(
+      &Rectangle::new((-4, -4), (8, 8)),
+      color.with_alpha(0.5),
+      Transform::translate((x, y)) * Transform::rotate(45),
+      0.0
+    );
+  }
+}
+
+pub fn draw_path(window: &mut Window, path: &Path) {
+  for track in path.debug() {
+    draw_track(window, track, Color::PURPLE);
+  }
+
+  // red if it runs over existing track and can't be built
+  let color = if path.is_valid() { Color::CYAN } else { Color::RED };
+
+  if let Some(pieces) = path.pieces() {
+    for track in pieces {
+      draw_track(window, track, color);
+    }
+  }
+
+  // current pos
+  window.draw(&Circle::new(path.start().pos.to_float(), 4), Color::RED);
+}
+
+pub fn to_color(Colour { r, g, b }: Colour) -> Color {
+  Color { r, g, b, a: 1.0 }
+}
+
+/// `alpha` is how far the simulation is between its last and next tick
+pub fn draw_train(window: &mut Window, train: &Train, alpha: f32) {
+  let colour = to_color(train.colour());
+  let segments = train.segments();
+
+  for seg in segments {
+    window.draw(&Circle::new(seg.lerp_pos(alpha), 5), colour);
+  }
+
+  for conn in segments.chunks(2) {
+    let (start, end) = (conn[0].lerp_pos(alpha), conn[1].lerp_pos(alpha));
+
+    draw_line(window, start.0, start.1, end.0, end.1, 10., colour);
+  }
+
+  // a light at the front shows which way the train is going
+  let (x, y) = segments[0].lerp_pos(alpha);
+  let (hx, hy) = segments[0].heading();
+  window.draw(&Circle::new((x + hx * 4., y + hy * 4.), 2), Color::WHITE);
+
+  match train.state() {
+    TrainState::Waiting(_) => {
+      window.draw(&Circle::new(segments[0].lerp_pos(alpha), 3), Color::RED);
+    }
+    TrainState::Crashed { at: (x, y), .. } => {
+      draw_line(window, x - CRASH_SIZE, y - CRASH_SIZE, x + CRASH_SIZE, y + CRASH_SIZE, 3., Color::RED);
+      draw_line(window, x - CRASH_SIZE, y + CRASH_SIZE, x + CRASH_SIZE, y - CRASH_SIZE, 3., Color::RED);
+    }
+    TrainState::Running | TrainState::Docked { .. } => {}
+  }
+}
+
+pub fn draw_signals(window: &mut Window, world: &World) {
+  for conn in world.signals.iter() {
+    let (x, y) = conn.pos.to_float();
+    let (dx, dy) = conn.dir.to_pos().to_float();
+    let len = (dx.powi(2) + dy.powi(2)).sqrt();
+
+    // to the right hand side of the direction of travel
+    let pos = (x - dy / len * SIGNAL_OFFSET, y + dx / len * SIGNAL_OFFSET);
+    let color = if world.signals.is_clear(conn, &world.connections, &world.switches, None) { Color::GREEN } else { Color::RED };
+
+    window.draw(&Circle::new(pos, 4), color);
+  }
+}
+
+/// Platforms with the station name and dwell time above them, next to the name
+/// a square in the colour of the train standing there
+pub fn draw_stations(window: &mut Window, world: &World) {
+  for (i, station) in world.stations.iter().enumerate() {
+    let (fx, fy) = station.from().to_float();
+    let (tx, ty) = station.to().to_float();
+
+    draw_line(window, fx, fy, tx, ty, PLATFORM_WIDTH, Color { r: 0.5, g: 0.5, b: 0.6, a: 0.4 });
+
+    let label = format!("{} {}S", station.name, station.dwell);
+    let width = text_width(&label, NAME_SCALE);
+    let height = 5. * NAME_SCALE;
+    let (x, y) = ((fx + tx) / 2. - width / 2., fy.min(ty) - PLATFORM_WIDTH / 2. - height - 2.);
+
+    draw_text(window, &label, (x, y), NAME_SCALE, Color::BLACK);
+
+    if let Some(train) = world.docked_at(i) {
+      window.draw(&Rectangle::new((x - height - NAME_SCALE * 2., y), (height, height)), to_color(train.colour()));
+    }
+  }
+}
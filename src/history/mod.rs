@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+
+use super::{
+  SwitchMap,
+  path::{
+    track::Track,
+    Connection,
+  },
+  station::Station,
+};
+
+/// Everything the player edits, connections and blocks are derived from it on restore
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+  pub tracks: Vec<Track>,
+  pub switches: SwitchMap,
+  pub signals: Vec<Connection>,
+  pub stations: Vec<Station>,
+}
+
+/// Undo/redo stacks of the network state from before each edit
+pub struct History {
+  undo: VecDeque<Snapshot>,
+  redo: Vec<Snapshot>,
+  depth: usize,
+}
+
+impl History {
+  pub fn new(depth: usize) -> Self {
+    History {
+      undo: VecDeque::new(),
+      redo: Vec::new(),
+      depth,
+    }
+  }
+
+  /// Records the state from before an edit, the oldest entry is dropped once `depth` is reached
+  pub fn push(&mut self, before: Snapshot) {
+    self.redo.clear();
+
+    if self.depth == 0 {
+      return;
+    }
+
+    if self.undo.len() == self.depth {
+      self.undo.pop_front();
+    }
+
+    self.undo.push_back(before);
+  }
+
+  pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+    let snapshot = self.undo.pop_back()?;
+    self.redo.push(current);
+
+    Some(snapshot)
+  }
+
+  pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+    let snapshot = self.redo.pop()?;
+    self.undo.push_back(current);
+
+    Some(snapshot)
+  }
+
+  pub fn clear(&mut self) {
+    self.undo.clear();
+    self.redo.clear();
+  }
+}
@@ -0,0 +1,373 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  fmt,
+  io,
+  str::{FromStr, SplitWhitespace},
+};
+
+use super::{
+  SwitchMap,
+  path::{
+    track::{
+      Track,
+      TrackPiece,
+    },
+    Connection,
+    Pos,
+  },
+  station::Station,
+  train::{
+    leaving,
+    Colour,
+    Performance,
+    Train,
+  },
+};
+
+// Layout files are plain text, one entry per line:
+//
+// quicksilver_train layout <version>
+// seed <seed> <rng position>
+// track <x> <y> <dir> <x> <y> <dir>
+// cross <x> <y> <dir> <x> <y> <dir>
+// switch <x> <y> <dir> <track>
+// signal <x> <y> <dir>
+// station <x> <y> <x> <y> <dwell> <name>
+// train <r> <g> <b> <max speed> <accel> <brake> <speed>
+// route <head dist> <track> <dir> [<track> <dir>...]
+// cars <offset>...
+//
+// routes and cars belong to the train line before them. A route lists the tracks
+// under the train from the last car up to the head, cars how far behind the head
+// the front and back of every car are. Tracks are referenced by their position in
+// the file and the connection map is regenerated on load.
+// Positions are lattice points, counted in half grid cells.
+
+const HEADER: &str = "quicksilver_train layout";
+pub const LAYOUT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum LayoutError {
+  Io(io::Error),
+  Header,
+  Version(u32),
+  Parse(usize, String),
+}
+
+impl fmt::Display for LayoutError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      LayoutError::Io(e) => write!(f, "{}", e),
+      LayoutError::Header => write!(f, "not a layout file"),
+      LayoutError::Version(v) => write!(f, "unsupported layout version {}, only version {} is known", v, LAYOUT_VERSION),
+      LayoutError::Parse(line, reason) => write!(f, "line {}: {}", line, reason),
+    }
+  }
+}
+
+impl From<io::Error> for LayoutError {
+  fn from(e: io::Error) -> Self {
+    LayoutError::Io(e)
+  }
+}
+
+pub struct Layout {
+  pub seed: u64,
+  pub rng_pos: u128,
+  pub tracks: Vec<Track>,
+  pub switches: SwitchMap,
+  pub signals: Vec<Connection>,
+  pub stations: Vec<Station>,
+  pub trains: Vec<Train>,
+}
+
+fn write_conn(out: &mut String, conn: &Connection) {
+  out.push_str(&format!(" {} {} {}", conn.pos.0, conn.pos.1, conn.dir));
+}
+
+pub fn save<'a>(seed: u64, rng_pos: u128, tracks: &[Track], switches: &SwitchMap, signals: impl Iterator<Item=&'a Connection>, stations: &[Station], trains: &[Train]) -> String {
+  let mut out = format!("{} {}\n", HEADER, LAYOUT_VERSION);
+  out.push_str(&format!("seed {} {}\n", seed, rng_pos));
+
+  for track in tracks {
+    out.push_str(if let Track::Cross(_) = track { "cross" } else { "track" });
+    write_conn(&mut out, &track.start());
+    write_conn(&mut out, &track.end());
+    out.push('\n');
+  }
+
+  // sorted so the same layout always saves to the same file
+  let mut switches = switches.iter().collect::<Vec<_>>();
+  switches.sort();
+
+  for (conn, trc) in switches {
+    out.push_str("switch");
+    write_conn(&mut out, conn);
+    out.push_str(&format!(" {}\n", trc));
+  }
+
+  let mut signals = signals.collect::<Vec<_>>();
+  signals.sort();
+
+  for conn in signals {
+    out.push_str("signal");
+    write_conn(&mut out, conn);
+    out.push('\n');
+  }
+
+  for station in stations {
+    let (from, to) = (station.from(), station.to());
+    out.push_str(&format!("station {} {} {} {} {} {}\n", from.0, from.1, to.0, to.1, station.dwell, station.name));
+  }
+
+  for train in trains {
+    let colour = train.colour();
+    let perf = train.performance();
+    out.push_str(&format!("train {} {} {} {} {} {} {}\n", colour.r, colour.g, colour.b, perf.max_speed, perf.accel, perf.brake, train.speed()));
+
+    out.push_str(&format!("route {}", train.head_dist()));
+    for (trc, dir) in train.route() {
+      out.push_str(&format!(" {} {}", trc, dir));
+    }
+    out.push('\n');
+
+    out.push_str("cars");
+    for offset in train.offsets() {
+      out.push_str(&format!(" {}", offset));
+    }
+    out.push('\n');
+  }
+
+  out
+}
+
+// a train line together with the route and cars lines after it
+struct TrainEntry {
+  line: usize,
+  colour: Colour,
+  performance: Performance,
+  speed: f32,
+  // head distance and route
+  route: Option<(f32, VecDeque<(usize, i8)>)>,
+  offsets: Vec<f32>,
+}
+
+struct Fields<'a> {
+  line: usize,
+  iter: SplitWhitespace<'a>,
+}
+
+impl<'a> Fields<'a> {
+  fn error(&self, reason: String) -> LayoutError {
+    LayoutError::Parse(self.line, reason)
+  }
+
+  fn next<T: FromStr>(&mut self, what: &str) -> Result<T, LayoutError> {
+    let field = self.iter.next().ok_or_else(|| self.error(format!("missing {}", what)))?;
+    field.parse().map_err(|_| self.error(format!("invalid {} {:?}", what, field)))
+  }
+
+  fn conn(&mut self) -> Result<Connection, LayoutError> {
+    let x = self.next("x")?;
+    let y = self.next("y")?;
+    let dir = self.next("direction")?;
+
+    Ok(Connection::new(Pos(x, y), dir))
+  }
+
+  fn track(&mut self, tracks: &[Track]) -> Result<usize, LayoutError> {
+    let trc: usize = self.next("track")?;
+
+    if trc < tracks.len() { Ok(trc) } else { Err(self.error(format!("unknown track {}", trc))) }
+  }
+}
+
+impl Layout {
+  pub fn parse(text: &str) -> Result<Layout, LayoutError> {
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim()));
+
+    let version = match lines.next() {
+      Some((_, line)) if line.starts_with(HEADER) => {
+        line[HEADER.len()..].trim().parse::<u32>().map_err(|_| LayoutError::Header)?
+      }
+      _ => return Err(LayoutError::Header),
+    };
+
+    if version != LAYOUT_VERSION {
+      return Err(LayoutError::Version(version));
+    }
+
+    let mut seed = 0;
+    let mut rng_pos = 0;
+    let mut tracks = Vec::new();
+    let mut switches = HashMap::new();
+    let mut signals = Vec::new();
+    let mut stations = Vec::new();
+    let mut trains: Vec<TrainEntry> = Vec::new();
+
+    for (line, text) in lines {
+      if text.is_empty() || text.starts_with('#') {
+        continue;
+      }
+
+      let mut fields = Fields { line, iter: text.split_whitespace() };
+
+      match fields.next::<String>("entry")?.as_str() {
+        "seed" => {
+          seed = fields.next("seed")?;
+          rng_pos = fields.next("rng position")?;
+        }
+        "track" => {
+          let start = fields.conn()?;
+          let end = fields.conn()?;
+          let track = Track::new(start, end)
+              .ok_or_else(|| fields.error(format!("can't join {} and {}", start.dir, end.dir)))?;
+
+          tracks.push(track);
+        }
+        "cross" => {
+          let start = fields.conn()?;
+          let end = fields.conn()?;
+          let track = Track::crossing(start, end)
+              .ok_or_else(|| fields.error(format!("can't cross from {} to {}", start.dir, end.dir)))?;
+
+          tracks.push(track);
+        }
+        "switch" => {
+          let conn = fields.conn()?;
+          let trc = fields.track(&tracks)?;
+
+          switches.insert(conn, trc);
+        }
+        "signal" => {
+          signals.push(fields.conn()?);
+        }
+        "station" => {
+          let from = Pos(fields.next("x")?, fields.next("y")?);
+          let to = Pos(fields.next("x")?, fields.next("y")?);
+          let dwell: f32 = fields.next("dwell time")?;
+          let name = fields.iter.clone().collect::<Vec<&str>>().join(" ");
+
+          if dwell < 0. {
+            return Err(fields.error(format!("invalid dwell time {}", dwell)));
+          }
+
+          if name.is_empty() {
+            return Err(fields.error("missing name".to_string()));
+          }
+
+          let station = Station::new(name, from, to, dwell)
+              .ok_or_else(|| fields.error(format!("platform from {:?} to {:?} isn't straight", from, to)))?;
+
+          stations.push(station);
+        }
+        "train" => {
+          let r = fields.next("red")?;
+          let g = fields.next("green")?;
+          let b = fields.next("blue")?;
+
+          let max_speed: f32 = fields.next("max speed")?;
+          let accel = fields.next("acceleration")?;
+          let brake = fields.next("braking")?;
+          let speed = fields.next("speed")?;
+
+          for &(what, value) in &[("max speed", max_speed), ("acceleration", accel), ("braking", brake), ("speed", speed)] {
+            if value.is_nan() || value < 0. {
+              return Err(fields.error(format!("invalid {} {}", what, value)));
+            }
+          }
+
+          // trains that can't brake would never stop for anything
+          if brake == 0. {
+            return Err(fields.error("braking can't be zero".to_string()));
+          }
+
+          let performance = Performance { max_speed, accel, brake };
+          trains.push(TrainEntry { line, colour: Colour { r, g, b }, performance, speed, route: None, offsets: Vec::new() });
+        }
+        "route" => {
+          let head_dist: f32 = fields.next("head distance")?;
+          let mut route = VecDeque::new();
+
+          while fields.iter.clone().next().is_some() {
+            let track = fields.track(&tracks)?;
+            let dir: i8 = fields.next("direction")?;
+
+            if dir != 1 && dir != -1 {
+              return Err(fields.error(format!("invalid direction {}", dir)));
+            }
+
+            // every track has to be entered where the one before it is left
+            if let Some(&(prev, prev_dir)) = route.back() {
+              let exit = leaving(&tracks[prev], prev_dir);
+              let mut entry = leaving(&tracks[track], -dir);
+              entry.dir = entry.dir.opposite();
+
+              if exit != entry {
+                return Err(fields.error(format!("track {} doesn't lead on to track {}", prev, track)));
+              }
+            }
+
+            route.push_back((track, dir));
+          }
+
+          let head = match route.back() {
+            Some(&(head, _)) => head,
+            None => return Err(fields.error("empty route".to_string())),
+          };
+
+          if head_dist < 0. || head_dist > tracks[head].len() {
+            return Err(fields.error(format!("distance {} is off track {}", head_dist, head)));
+          }
+
+          match trains.last_mut() {
+            Some(entry) => entry.route = Some((head_dist, route)),
+            None => return Err(fields.error("route without a train".to_string())),
+          }
+        }
+        "cars" => {
+          let mut offsets: Vec<f32> = Vec::new();
+
+          while fields.iter.clone().next().is_some() {
+            let offset = fields.next("offset")?;
+
+            if offset < offsets.last().cloned().unwrap_or(0.) {
+              return Err(fields.error(format!("offset {} is ahead of the car end before it", offset)));
+            }
+
+            offsets.push(offset);
+          }
+
+          match trains.last_mut() {
+            Some(entry) => entry.offsets = offsets,
+            None => return Err(fields.error("cars without a train".to_string())),
+          }
+        }
+        entry => return Err(fields.error(format!("unknown entry {:?}", entry))),
+      }
+    }
+
+    let mut loaded = Vec::new();
+
+    for TrainEntry { line, colour, performance, speed, route, offsets } in trains {
+      // car ends come in pairs, the front and the back of every car
+      if offsets.is_empty() || offsets.len() % 2 != 0 {
+        return Err(LayoutError::Parse(line, "trains need an even number of car ends".to_string()));
+      }
+
+      let (head_dist, route) = route.ok_or_else(|| LayoutError::Parse(line, "train without a route".to_string()))?;
+
+      loaded.push(Train::from_route(route, head_dist, offsets, colour, performance, speed, &tracks));
+    }
+
+    Ok(Layout {
+      seed,
+      rng_pos,
+      tracks,
+      switches,
+      signals,
+      stations,
+      trains: loaded,
+    })
+  }
+}
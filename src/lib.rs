@@ -0,0 +1,66 @@
+extern crate rand;
+extern crate rand_chacha;
+
+pub mod path;
+pub mod train;
+pub mod signal;
+pub mod station;
+pub mod layout;
+pub mod history;
+pub mod world;
+pub mod clock;
+
+use std::collections::HashMap;
+
+use path::{
+  track::{
+    Track,
+    TrackPiece,
+  },
+  Pos,
+  Connection,
+  LATTICE_STEP,
+};
+
+pub const GRID_CELL_SIZE: f32 = 32.;
+
+pub type ConnectionMap = HashMap<Connection, Vec<(usize, i8)>>;
+// junction connection -> track index of the active branch
+pub type SwitchMap = HashMap<Connection, usize>;
+
+/// The branch a train takes at a connection, falls back to the first one if the switch isn't set
+pub fn active_branch<'a>(conns: &'a [(usize, i8)], switch: Option<&usize>) -> &'a (usize, i8) {
+  switch
+      .and_then(|trc| conns.iter().find(|(i, _)| i == trc))
+      .unwrap_or_else(|| conns.first().expect("Connections should have at least one element"))
+}
+
+pub fn connect_track(connections: &mut ConnectionMap, i: usize, track: &Track) {
+  let sta = track.start();
+  let mut end = track.end();
+  end.dir = end.dir.opposite();
+
+  connections.entry(sta).or_default().push((i, 1));
+  connections.entry(end).or_default().push((i, -1));
+}
+
+/// The edge midpoint closest to a point in pixels
+pub fn snap_to_grid(pos: (f32, f32)) -> Pos {
+  let gs = GRID_CELL_SIZE;
+
+  // tile offset, kept positive so the left and top of the world snap the same way
+  let off = (((pos.0 % gs) + gs) % gs, ((pos.1 % gs) + gs) % gs);
+  // grid offset
+  let (rx, ry) = (pos.0 - off.0, pos.1 - off.1);
+  // relative offset
+  let (x, y) = (off.0 / gs, off.1 / gs);
+
+  let res = match (x > y, x + y < 1.) {
+    (true, true) => (rx + gs / 2., ry),
+    (true, false) => (rx + gs, ry + gs / 2.),
+    (false, true) => (rx, ry + gs / 2.),
+    (false, false) => (rx + gs / 2., ry + gs),
+  };
+
+  Pos((res.0 / LATTICE_STEP).round() as i32, (res.1 / LATTICE_STEP).round() as i32)
+}
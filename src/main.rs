@@ -1,116 +1,377 @@
-#![feature(slice_patterns)]
-#![feature(vec_remove_item)]
-
-extern crate rand;
 extern crate quicksilver;
-
-mod path;
-mod train;
-
-use std::collections::HashMap;
+extern crate quicksilver_train;
+#[cfg(target_arch = "wasm32")]
+extern crate stdweb;
+
+mod draw;
+mod camera;
+mod panel;
+
+// there's no file system to save layouts to on the web
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+use stdweb::web::Date;
 
 use quicksilver::{
   Result,
-  geom::{Rectangle, Vector, Circle, Transform},
-  input::{MouseButton, ButtonState},
-  graphics::{Color},
+  geom::{Rectangle, Vector, Circle, Shape},
+  input::{MouseButton, ButtonState, Key},
+  graphics::{Color, View},
   lifecycle::{run, Event, Settings, State, Window},
 };
 
-use path::{
-  track::{
-    Track,
-    TrackPiece,
+use quicksilver_train::{
+  path::{
+    track::TrackPiece,
+    obstacles::Obstacles,
+    Path,
+    PathStyle,
+    SearchStats,
+    Dir,
+    Pos,
+    Slot,
+    Connection,
   },
-  Path,
-  Dir,
-  Pos,
-  Connection,
+  train::{Consist, SpawnError},
+  history::History,
+  world::World,
+  clock::{Clock, TICK},
+  active_branch,
+  snap_to_grid,
+  GRID_CELL_SIZE,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use quicksilver_train::layout::LayoutError;
+
+use draw::{draw_line, draw_track, draw_path, draw_train, draw_signals, draw_stations};
+use camera::Camera;
+use panel::SpawnPanel;
+
+#[cfg(not(target_arch = "wasm32"))]
+const LAYOUT_FILE: &str = "layout.txt";
+
+// number of edits that can be undone
+const HISTORY_DEPTH: usize = 100;
+
+// most ticks simulated per update before the simulation starts lagging behind
+const MAX_TICKS: u32 = 8;
+
+// max distance from the cursor for a track to get picked by the bulldozer
+const BULLDOZE_RADIUS: f32 = GRID_CELL_SIZE / 4.;
+
+// max distance from the cursor for a junction or signal to get picked
+const SWITCH_RADIUS: f32 = GRID_CELL_SIZE / 2.;
+
+// max distance from the cursor for a station to get picked
+const STATION_RADIUS: f32 = GRID_CELL_SIZE / 2.;
+
+// max distance from the cursor for a track to get a train spawned on
+const SPAWN_RADIUS: f32 = GRID_CELL_SIZE / 2.;
 
-use train::Train;
+// how long a refused spawn stays marked, in seconds
+const REFUSED_TIME: f64 = 0.5;
 
-const GRID_CELL_SIZE: f32 = 32.;
+// screen pixels per second the camera moves while an arrow key is held
+const PAN_SPEED: f32 = 600.;
 
-type ConnectionMap = HashMap<Connection, Vec<(usize, i8)>>;
+// zoom factor for one line of mouse wheel scrolling
+const ZOOM_STEP: f32 = 1.1;
+// quicksilver reports wheel lines as this many pixels
+const WHEEL_LINE: f32 = 15.;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Tool {
+  Build,
+  Bulldoze,
+  Switch,
+  Signal,
+  Station,
+}
 
 struct GameState {
   mouse_pos: Pos,
-  cam_pos: Pos,
+  camera: Camera,
+  // world position held by the middle mouse button while panning
+  pan_grab: Option<Vector>,
+  tool: Tool,
+  drag_start: Option<Vector>,
   path: Option<Path>,
-  tracks: Vec<Track>,
-  trains: Vec<Train>,
-  connections: ConnectionMap,
+  path_style: PathStyle,
+  world: World,
+  history: History,
+  // what the next spawned train looks like
+  consist: Consist,
+  panel: SpawnPanel,
+  // where the right mouse button went down, the train faces towards where it's released
+  spawn_start: Option<Vector>,
+  // position and time of the last refused spawn
+  refused: Option<(Vector, f64)>,
+  clock: Clock,
+  last_update: f64,
+  // stats and duration in seconds of the last path search
+  last_search: Option<(SearchStats, f64)>,
+  // currently shown in the window title
+  shown_title: String,
+}
+
+// seconds since some fixed point in time
+#[cfg(not(target_arch = "wasm32"))]
+fn current_time() -> f64 {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("the clock is set after 1970");
+  now.as_secs() as f64 + now.subsec_nanos() as f64 * 1e-9
+}
+
+#[cfg(target_arch = "wasm32")]
+fn current_time() -> f64 {
+  Date::now() / 1000.
+}
+
+// the cursor in screen pixels, whatever view the window has
+fn screen_cursor(window: &Window) -> Vector {
+  window.unproject() * window.mouse().pos()
 }
 
 impl GameState {
   pub fn new() -> Self {
     GameState {
       mouse_pos: Pos(0, 0),
+      camera: Camera::new(),
+      pan_grab: None,
+      tool: Tool::Build,
+      drag_start: None,
       path: None,
-      tracks: Vec::new(),
-      trains: Vec::new(),
-      cam_pos: Pos(0, 0),
-      connections: HashMap::new(),
+      path_style: PathStyle::Shortest,
+      world: World::new((current_time() * 1000.) as u64),
+      history: History::new(HISTORY_DEPTH),
+      consist: Consist {
+        speed: 250.,
+        accel: 100.,
+        brake: 200.,
+        cars: 4,
+        car_len: 20.,
+        gap: 5.,
+      },
+      panel: SpawnPanel::new(),
+      spawn_start: None,
+      refused: None,
+      clock: Clock::new(TICK, MAX_TICKS),
+      last_update: current_time(),
+      last_search: None,
+      shown_title: String::new(),
     }
   }
-}
 
-pub(crate) fn draw_line(window: &mut Window, x: f32, y: f32, ex: f32, ey: f32, width: f32, color: Color) {
-  let is_x = x != ex;
-  let diagonal = is_x && y != ey;
-
-  let dx = ex - x;
-  let dy = ey - y;
-
-  let len = if diagonal {
-    (dx.abs().powi(2) + dy.abs().powi(2)).sqrt()
-  } else if is_x {
-    (ex - x).abs()
-  } else {
-    (ey - y).abs()
-  };
-
-  let angle = if diagonal {
-    (180. * (dx / -dy).atan() / std::f32::consts::PI) - 90.
-  } else if is_x {
-    0.
-  } else {
-    90.
-  };
-
-  let center = Vector::new(x + dx / 2.0, y + dy / 2.0);
-  // drawing is top left so we have to offset
-  let half_width = width / 2.;
-  let off = Vector::new(-len / 2., -half_width);
-
-  window.draw_ex(
-    &Rectangle::new((0, 0), (len, width)),
-    color,
-    Transform::translate(center + off) * Transform::rotate(angle),
-    0.0
-  );
-}
+  fn set_tool(&mut self, tool: Tool) {
+    self.path = None;
+    self.drag_start = None;
+    self.tool = if self.tool == tool { Tool::Build } else { tool };
+  }
+
+  /// The connection closest to the cursor, connections sharing a position
+  /// are picked by which side of it the cursor is on
+  fn pick_connection<'a>(cursor: Vector, conns: impl Iterator<Item=(&'a Connection, &'a Vec<(usize, i8)>)>) -> Option<Connection> {
+    conns
+        .filter_map(|(conn, _)| {
+          let (x, y) = conn.pos.to_float();
+          let (dx, dy) = conn.dir.to_pos().to_float();
+          let off = Vector::new(cursor.x - x, cursor.y - y);
+
+          if off.len() < SWITCH_RADIUS { Some((*conn, off.x * dx + off.y * dy)) } else { None }
+        })
+        // ties are broken by the connection itself so picking doesn't depend on hash order
+        .max_by(|a, b| a.1.partial_cmp(&b.1).expect("distances should never be NaN").then(a.0.cmp(&b.0)))
+        .map(|(conn, _)| conn)
+  }
+
+  /// Cycles the junction closest to the cursor to its next branch
+  fn toggle_switch(&mut self, cursor: Vector) {
+    if let Some(conn) = GameState::pick_connection(cursor, self.world.junctions()) {
+      self.record();
+      self.world.toggle_switch(conn);
+    }
+  }
+
+  /// Places or removes a signal for trains passing the connection closest to the cursor
+  fn toggle_signal(&mut self, cursor: Vector) {
+    if let Some(conn) = GameState::pick_connection(cursor, self.world.connections.iter()) {
+      self.record();
+      self.world.toggle_signal(conn);
+    }
+  }
+
+  /// Dragging along track from `start` to `cursor` places a station, clicking a
+  /// station cycles its dwell time or removes it if `remove` is set
+  fn edit_station(&mut self, start: Vector, cursor: Vector, remove: bool) {
+    let from = snap_to_grid((start.x.floor(), start.y.floor()));
+    let to = snap_to_grid((cursor.x.floor(), cursor.y.floor()));
+
+    if from == to {
+      if let Some(i) = self.world.station_at((cursor.x, cursor.y), STATION_RADIUS) {
+        self.record();
+
+        if remove {
+          self.world.remove_station(i);
+        } else {
+          self.world.stations[i].next_dwell();
+        }
+      }
+      return;
+    }
+
+    if self.world.station_allowed(from, to) {
+      self.record();
+      self.world.add_station(from, to);
+    }
+  }
+
+  /// Snaps the cursor to the grid and extends the path being built
+  fn move_cursor(&mut self, Vector { x, y }: Vector) {
+    let snap = snap_to_grid((x.floor(), y.floor()));
+
+    if snap == self.mouse_pos {
+      return;
+    }
 
-fn snap_to_grid(pos: Pos) -> Pos {
-  let gs = GRID_CELL_SIZE as f32;
-  let pos = (pos.0 as f32, pos.1 as f32);
-
-  // tile offset
-  let off = (pos.0 % gs, pos.1 % gs);
-  // grid offset
-  let (rx, ry) = (pos.0 - off.0, pos.1 - off.1);
-  // relative offset
-  let (x, y) = (off.0 / gs, off.1 / gs);
-
-  let res = match (x > y, x + y < 1.) {
-    (true, true) => (rx + gs / 2., ry),
-    (true, false) => (rx + gs, ry + gs / 2.),
-    (false, true) => (rx, ry + gs / 2.),
-    (false, false) => (rx + gs / 2., ry + gs),
-  };
-
-  Pos(res.0 as i32, res.1 as i32)
+    self.mouse_pos = snap;
+    self.update_path();
+  }
+
+  /// Direction a path starting at `start` leaves in. On existing track it continues
+  /// the track, preferring open ends, otherwise it's picked by which side of the
+  /// point the cursor is on.
+  fn start_dir(&self, start: Pos, cursor: Vector) -> Dir {
+    let (x, y) = start.to_float();
+    let off = Vector::new(cursor.x - x, cursor.y - y);
+
+    let inherited = self.world.exits_at(start)
+        .into_iter()
+        .map(|(conn, open)| {
+          let (dx, dy) = conn.dir.to_pos().to_float();
+          (conn.dir, open, off.x * dx + off.y * dy)
+        })
+        .max_by(|a, b| a.1.cmp(&b.1).then(a.2.partial_cmp(&b.2).expect("distances should never be NaN")))
+        .map(|(dir, _, _)| dir);
+
+    if let Some(dir) = inherited {
+      return dir;
+    }
+
+    // straights through the middle of vertical edges run sideways
+    if start.slot() == Slot::VerticalEdge {
+      if off.x > 0. { Dir::Right } else { Dir::Left }
+    } else {
+      if off.y > 0. { Dir::Up } else { Dir::Down }
+    }
+  }
+
+  /// Searches the path being built towards the cursor again
+  fn update_path(&mut self) {
+    if let Some(ref mut path) = self.path {
+      let start = current_time();
+      path.add_path(self.mouse_pos);
+      self.last_search = Some((path.stats(), current_time() - start));
+    }
+  }
+
+  /// The cursor stays put on screen when the camera moves, so it now points
+  /// somewhere else in the world
+  fn follow_camera(&mut self, window: &Window) {
+    let cursor = self.cursor(window);
+    self.move_cursor(cursor);
+  }
+
+  /// World position of the cursor through the camera. Mouse positions from the
+  /// window are in the view it had when events were polled, which lags behind
+  /// the camera until the next draw, only the screen position stays right.
+  fn cursor(&self, window: &Window) -> Vector {
+    self.camera.to_world(screen_cursor(window))
+  }
+
+  /// Tracks that would be removed by the bulldozer, either everything with its
+  /// center in the dragged rectangle or the piece closest to the cursor
+  fn bulldoze_selection(&self, window: &Window) -> Vec<usize> {
+    let cursor = self.cursor(window);
+
+    match self.drag_start {
+      Some(start) if (cursor - start).len() > BULLDOZE_RADIUS => {
+        let min = Vector::new(start.x.min(cursor.x), start.y.min(cursor.y));
+        let max = Vector::new(start.x.max(cursor.x), start.y.max(cursor.y));
+        let rect = Rectangle::new(min, max - min);
+
+        self.world.tracks.iter()
+            .enumerate()
+            .filter(|(_, track)| rect.contains(track.lerp(0.5)))
+            .map(|(i, _)| i)
+            .collect()
+      }
+      _ => {
+        self.world.track_at((cursor.x, cursor.y), BULLDOZE_RADIUS)
+            .map(|(i, _)| vec![i])
+            .unwrap_or_default()
+      }
+    }
+  }
+
+  /// Spawns a train on the track under `start` facing along the track towards `cursor`
+  fn spawn_train(&mut self, start: Vector, cursor: Vector) -> std::result::Result<(), SpawnError> {
+    let (trc, dist) = self.world.track_at((start.x, start.y), SPAWN_RADIUS).ok_or(SpawnError::NoTrack)?;
+
+    // which way the track runs at the spawn point
+    let track = &self.world.tracks[trc];
+    let (tx, ty) = track.tangent(dist / track.len());
+    let along = tx * (cursor.x - start.x) + ty * (cursor.y - start.y);
+
+    let dir = if along < 0. { -1 } else { 1 };
+
+    self.world.spawn_train(trc, dist, dir, &self.consist)
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn save_layout(&self, file: &str) -> std::result::Result<(), LayoutError> {
+    fs::write(file, self.world.save())?;
+
+    Ok(())
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn load_layout(&mut self, file: &str) -> std::result::Result<(), LayoutError> {
+    self.world = World::load(&fs::read_to_string(file)?)?;
+
+    self.path = None;
+    self.drag_start = None;
+    self.history.clear();
+
+    Ok(())
+  }
+
+  /// Saves the current network so the next edit can be undone
+  fn record(&mut self) {
+    let snapshot = self.world.snapshot();
+    self.history.push(snapshot);
+  }
+
+  pub fn undo(&mut self) {
+    let current = self.world.snapshot();
+
+    if let Some(snapshot) = self.history.undo(current) {
+      self.path = None;
+      self.drag_start = None;
+      self.world.restore(snapshot);
+    }
+  }
+
+  pub fn redo(&mut self) {
+    let current = self.world.snapshot();
+
+    if let Some(snapshot) = self.history.redo(current) {
+      self.path = None;
+      self.drag_start = None;
+      self.world.restore(snapshot);
+    }
+  }
 }
 
 impl State for GameState {
@@ -119,8 +380,25 @@ impl State for GameState {
   }
 
   fn update(&mut self, window: &mut Window) -> Result<()> {
-    for train in self.trains.iter_mut() {
-      train.update(window, &self.tracks, &self.connections);
+    let now = current_time();
+
+    let keys = window.keyboard();
+    let mut pan = Vector::ZERO;
+    if keys[Key::Left].is_down() { pan.x -= 1.; }
+    if keys[Key::Right].is_down() { pan.x += 1.; }
+    if keys[Key::Up].is_down() { pan.y -= 1.; }
+    if keys[Key::Down].is_down() { pan.y += 1.; }
+
+    if pan != Vector::ZERO {
+      self.camera.pan(pan * PAN_SPEED * (now - self.last_update) as f32);
+      self.follow_camera(window);
+    }
+
+    let ticks = self.clock.advance((now - self.last_update) as f32);
+    self.last_update = now;
+
+    for _ in 0..ticks {
+      self.world.update(self.clock.tick());
     }
 
     Ok(())
@@ -128,27 +406,136 @@ impl State for GameState {
 
   fn event(&mut self, evt: &Event, window: &mut Window) -> Result<()> {
     let Pos(x, y) = self.mouse_pos;
-    let Vector { x: mx, y: my } = window.mouse().pos();
+    let screen_pos = screen_cursor(window);
+    let Vector { x: mx, y: my } = self.cursor(window);
+    let ctrl = window.keyboard()[Key::LControl].is_down() || window.keyboard()[Key::RControl].is_down();
 
     match evt {
-      Event::MouseMoved(Vector { x, y }) => {
-//        if window.mouse()[MouseButton::Middle].is_down() {
-//          self.cam_pos.0 += dx;
-//          self.cam_pos.1 += dy;
-//        }
-
-        let Pos(cx, cy) = self.cam_pos;
-
-        let snap = snap_to_grid(Pos(*x as i32 + cx, *y as i32 + cy));
-
-        if snap == self.mouse_pos {
-          return Ok(());
+      // the window already took in the position, it's read back in screen space
+      Event::MouseMoved(_) => {
+        if let Some(grab) = self.pan_grab {
+          self.camera.pan(grab - screen_pos);
+          self.pan_grab = Some(screen_pos);
         }
-
-        self.mouse_pos = snap;
+        self.follow_camera(window);
+      }
+      Event::MouseWheel(Vector { y, .. }) => {
+        self.camera.zoom_at(Vector::new(mx, my), ZOOM_STEP.powf(-y / WHEEL_LINE));
+        self.follow_camera(window);
+      }
+      Event::MouseButton(MouseButton::Middle, ButtonState::Pressed) => {
+        self.pan_grab = Some(screen_pos);
+      }
+      Event::MouseButton(MouseButton::Middle, ButtonState::Released) => {
+        self.pan_grab = None;
+      }
+      Event::Key(Key::B, ButtonState::Pressed) => {
+        self.set_tool(Tool::Bulldoze);
+      }
+      Event::Key(Key::J, ButtonState::Pressed) => {
+        self.set_tool(Tool::Switch);
+      }
+      Event::Key(Key::S, ButtonState::Pressed) => {
+        self.set_tool(Tool::Signal);
+      }
+      Event::Key(Key::N, ButtonState::Pressed) => {
+        self.set_tool(Tool::Station);
+      }
+      Event::Key(Key::P, ButtonState::Pressed) => {
+        self.path_style = self.path_style.next();
 
         if let Some(ref mut path) = self.path {
-          path.add_path(snap);
+          path.set_style(self.path_style);
+        }
+        self.update_path();
+      }
+      Event::Key(Key::T, ButtonState::Pressed) => {
+        self.panel.visible = !self.panel.visible;
+      }
+      Event::Key(Key::W, ButtonState::Pressed) => {
+        self.world.remove_wrecks = !self.world.remove_wrecks;
+      }
+      Event::Key(Key::Z, ButtonState::Pressed) if ctrl => {
+        self.undo();
+      }
+      Event::Key(Key::Y, ButtonState::Pressed) if ctrl => {
+        self.redo();
+      }
+      #[cfg(not(target_arch = "wasm32"))]
+      Event::Key(Key::F5, ButtonState::Pressed) => {
+        if let Err(e) = self.save_layout(LAYOUT_FILE) {
+          eprintln!("failed to save {}: {}", LAYOUT_FILE, e);
+        }
+      }
+      #[cfg(not(target_arch = "wasm32"))]
+      Event::Key(Key::F9, ButtonState::Pressed) => {
+        if let Err(e) = self.load_layout(LAYOUT_FILE) {
+          eprintln!("failed to load {}: {}", LAYOUT_FILE, e);
+        }
+      }
+      Event::Key(Key::Escape, ButtonState::Pressed) => {
+        self.path = None;
+        self.drag_start = None;
+        self.spawn_start = None;
+      }
+      // the panel takes the release too so it doesn't reach the tools underneath
+      Event::MouseButton(MouseButton::Left, state) if self.panel.contains(screen_pos) => {
+        match state {
+          ButtonState::Pressed => {
+            self.panel.click(screen_pos, &mut self.consist);
+          }
+          // drags released over the panel get dropped
+          ButtonState::Released => {
+            self.drag_start = None;
+          }
+          _ => {}
+        }
+      }
+      Event::MouseButton(MouseButton::Right, ButtonState::Pressed) => {
+        self.spawn_start = Some(Vector::new(mx, my));
+      }
+      Event::MouseButton(MouseButton::Right, ButtonState::Released) => {
+        if let Some(start) = self.spawn_start.take() {
+          if let Err(e) = self.spawn_train(start, Vector::new(mx, my)) {
+            eprintln!("can't spawn train: {}", e);
+            self.refused = Some((start, current_time()));
+          }
+        }
+      }
+      Event::MouseButton(MouseButton::Left, state) if self.tool == Tool::Bulldoze => {
+        match state {
+          ButtonState::Pressed => {
+            self.drag_start = Some(Vector::new(mx, my));
+          }
+          ButtonState::Released if self.drag_start.is_some() => {
+            let removed = self.bulldoze_selection(window);
+            self.drag_start = None;
+
+            if !removed.is_empty() {
+              self.record();
+              self.world.remove_tracks(&removed);
+            }
+          }
+          _ => {}
+        }
+      }
+      Event::MouseButton(MouseButton::Left, ButtonState::Pressed) if self.tool == Tool::Switch => {
+        self.toggle_switch(Vector::new(mx, my));
+      }
+      Event::MouseButton(MouseButton::Left, ButtonState::Pressed) if self.tool == Tool::Signal => {
+        self.toggle_signal(Vector::new(mx, my));
+      }
+      Event::MouseButton(MouseButton::Left, state) if self.tool == Tool::Station => {
+        match state {
+          ButtonState::Pressed => {
+            self.drag_start = Some(Vector::new(mx, my));
+          }
+          ButtonState::Released => {
+            if let Some(start) = self.drag_start.take() {
+              self.edit_station(start, Vector::new(mx, my), ctrl);
+            }
+          }
+          _ => {}
         }
       }
       Event::MouseButton(button, state) => {
@@ -159,12 +546,12 @@ impl State for GameState {
         match button {
           MouseButton::Left => {
             if self.path.is_none() {
-              let is_x = x % GRID_CELL_SIZE as i32 == 0;
-              self.path = Some(Path::new(Pos(x, y), if is_x {
-                if mx as i32 > x { Dir::Right } else { Dir::Left }
-              } else {
-                if my as i32 > y { Dir::Up } else { Dir::Down }
-              }));
+              let mut path = Path::new(Pos(x, y), self.start_dir(Pos(x, y), Vector::new(mx, my)));
+              path.set_style(self.path_style);
+              path.set_obstacles(Obstacles::new(&self.world.tracks));
+
+              self.path = Some(path);
+              self.last_search = None;
               return Ok(());
             }
 
@@ -173,24 +560,11 @@ impl State for GameState {
             let path = path.expect("we checked for none");
 
             if let Some(pieces) = path.into_pieces() {
-              for track in pieces {
-                let sta = track.start();
-                let mut end = track.end();
-                end.dir = end.dir.opposite();
-
-                let i = self.tracks.len();
-
-                self.connections.entry(sta).or_insert(Vec::new()).push((i, 1));
-                self.connections.entry(end).or_insert(Vec::new()).push((i, -1));
-
-                self.tracks.push(track)
-              }
+              self.record();
+              self.world.add_tracks(pieces);
             }
           }
-          MouseButton::Right => {
-            self.trains.push(Train::new(250., 0, 0., (4, 5., 20.), &self.tracks, &self.connections));
-          }
-          MouseButton::Middle => {}
+          MouseButton::Right | MouseButton::Middle => {}
         }
       }
       _ => {}
@@ -200,43 +574,123 @@ impl State for GameState {
   }
 
   fn draw(&mut self, window: &mut Window) -> Result<()> {
+    let wrecks = if self.world.remove_wrecks { "cleared" } else { "kept" };
+    let mut title = format!("Trains! (seed {}, paths: {}, wrecks: {})", self.world.seed(), self.path_style, wrecks);
+
+    if let (Some(_), Some((stats, time))) = (&self.path, self.last_search) {
+      title += &format!(" - path search: {} nodes in {:.1}ms{}", stats.expanded, time * 1000., if stats.exhausted { ", out of budget" } else { "" });
+    }
+
+    if self.shown_title != title {
+      window.set_title(&title);
+      self.shown_title = title;
+    }
+
     window.clear(Color::WHITE)?;
 
-    let screen_size = window.screen_size();
+    window.set_view(self.camera.view(window.screen_size()));
+
+    let visible = self.camera.visible(window.screen_size());
+    let (min, max) = (visible.top_left(), visible.top_left() + visible.size());
+    // keep grid lines one screen pixel wide at any zoom
+    let width = 1. / self.camera.zoom;
 
-    for i in 0..((screen_size.x / GRID_CELL_SIZE).ceil() as i16) {
+    for i in ((min.x / GRID_CELL_SIZE).floor() as i32)..((max.x / GRID_CELL_SIZE).ceil() as i32) {
       let x: f32 = i as f32 * GRID_CELL_SIZE;
-      let y: f32 = screen_size.y;
 
-      draw_line(window, x, 0., x, y, 1., Color::BLACK.with_alpha(0.3));
+      draw_line(window, x, min.y, x, max.y, width, Color::BLACK.with_alpha(0.3));
 //      window.draw(&Line::new((x, 0.), (x, y)), Color::BLACK.with_alpha(0.3));
     }
 
-    for i in 0..((screen_size.y / GRID_CELL_SIZE).ceil() as i16) {
-      let x: f32 = screen_size.x;
+    for i in ((min.y / GRID_CELL_SIZE).floor() as i32)..((max.y / GRID_CELL_SIZE).ceil() as i32) {
       let y: f32 = i as f32 * GRID_CELL_SIZE;
 
-      draw_line(window, 0., y, x, y, 1., Color::BLACK.with_alpha(0.3));
+      draw_line(window, min.x, y, max.x, y, width, Color::BLACK.with_alpha(0.3));
 //      window.draw(&Line::new((0., y), (x, y)), Color::BLACK.with_alpha(0.3));
     }
 //
-    for track in self.tracks.iter() {
-      track.draw(window, Color::BLACK);
+    let selection = match self.tool {
+      Tool::Bulldoze => self.bulldoze_selection(window),
+      _ => Vec::new(),
+    };
+
+    for (i, track) in self.world.tracks.iter().enumerate() {
+      draw_track(window, track, if selection.contains(&i) { Color::RED } else { Color::BLACK });
     }
 
-    for train in self.trains.iter_mut() {
-      train.draw(window);
+    // highlight the branch each junction is set to
+    for (conn, conns) in self.world.junctions() {
+      let (trc, _) = active_branch(conns, self.world.switches.get(conn));
+
+      if !selection.contains(trc) {
+        draw_track(window, &self.world.tracks[*trc], Color::GREEN);
+      }
+
+      window.draw(&Circle::new(conn.pos.to_float(), 3), Color::GREEN);
+    }
+
+    draw_signals(window, &self.world);
+    draw_stations(window, &self.world);
+
+    match (self.drag_start, self.tool) {
+      // the platform that gets placed
+      (Some(start), Tool::Station) => {
+        let from = snap_to_grid((start.x.floor(), start.y.floor()));
+        let color = if self.world.station_allowed(from, self.mouse_pos) { Color::BLUE } else { Color::RED };
+        let ((fx, fy), (tx, ty)) = (from.to_float(), self.mouse_pos.to_float());
+
+        draw_line(window, fx, fy, tx, ty, 6., color.with_alpha(0.5));
+      }
+      (Some(start), _) => {
+        let cursor = self.cursor(window);
+        let min = Vector::new(start.x.min(cursor.x), start.y.min(cursor.y));
+        let max = Vector::new(start.x.max(cursor.x), start.y.max(cursor.y));
+
+        window.draw(&Rectangle::new(min, max - min), Color::RED.with_alpha(0.2));
+      }
+      (None, _) => {}
+    }
+
+    let alpha = self.clock.alpha((current_time() - self.last_update) as f32);
+
+    for train in self.world.trains.iter() {
+      draw_train(window, train, alpha);
     }
 
     if let Some(ref path) = self.path {
-      path.draw(window);
+      draw_path(window, path);
+    }
+
+    if let Some(start) = self.spawn_start {
+      let cursor = self.cursor(window);
+
+      window.draw(&Circle::new(start, 4), Color::BLUE);
+      draw_line(window, start.x, start.y, cursor.x, cursor.y, 2., Color::BLUE.with_alpha(0.5));
+    }
+
+    if let Some((pos, time)) = self.refused {
+      if current_time() - time < REFUSED_TIME {
+        window.draw(&Circle::new(pos, SPAWN_RADIUS), Color::RED.with_alpha(0.5));
+      } else {
+        self.refused = None;
+      }
     }
 
 //    draw_line(window, 32., 32., 64., 32., 4., Color::BLACK);
 //    draw_line(window, 32., 32., 32., 64., 4., Color::BLACK);
 //    draw_line(window, 32., 32., 64., 64., 4., Color::BLACK);
 
-    window.draw(&Circle::new((self.mouse_pos.0, self.mouse_pos.1), 8), Color::PURPLE);
+    match self.tool {
+      Tool::Build => window.draw(&Circle::new(self.mouse_pos.to_float(), 8), Color::PURPLE),
+      Tool::Bulldoze => window.draw(&Circle::new(self.cursor(window), BULLDOZE_RADIUS), Color::RED.with_alpha(0.5)),
+      Tool::Switch => window.draw(&Circle::new(self.cursor(window), SWITCH_RADIUS), Color::GREEN.with_alpha(0.3)),
+      Tool::Signal => window.draw(&Circle::new(self.cursor(window), SWITCH_RADIUS), Color::RED.with_alpha(0.3)),
+      Tool::Station => window.draw(&Circle::new(self.mouse_pos.to_float(), 4), Color::BLUE),
+    }
+
+    window.set_view(View::new(Rectangle::new_sized(window.screen_size())));
+    self.panel.draw(window, &self.consist);
+    window.set_view(self.camera.view(window.screen_size()));
 
 //    window.present();
 
@@ -0,0 +1,173 @@
+use quicksilver::{
+  geom::{Rectangle, Vector, Shape},
+  graphics::{Color},
+  lifecycle::{Window},
+};
+
+use quicksilver_train::train::Consist;
+
+// top left corner of the panel on screen
+const PANEL_POS: (f32, f32) = (10., 10.);
+const ROW_HEIGHT: f32 = 24.;
+const BUTTON_SIZE: f32 = 20.;
+const BAR_WIDTH: f32 = 120.;
+const PADDING: f32 = 4.;
+// screen pixels per world pixel in the consist preview
+const PREVIEW_SCALE: f32 = 0.5;
+
+/// One adjustable value of the consist, there are no fonts so every
+/// row gets its own colour instead of a label
+#[derive(Debug, Copy, Clone)]
+enum Field {
+  Speed,
+  Accel,
+  Brake,
+  Cars,
+  CarLen,
+  Gap,
+}
+
+const FIELDS: [Field; 6] = [Field::Speed, Field::Accel, Field::Brake, Field::Cars, Field::CarLen, Field::Gap];
+
+impl Field {
+  // min, max and step of a click
+  fn range(self) -> (f32, f32, f32) {
+    match self {
+      Field::Speed => (25., 500., 25.),
+      Field::Accel => (25., 500., 25.),
+      Field::Brake => (50., 1000., 50.),
+      Field::Cars => (1., 12., 1.),
+      Field::CarLen => (5., 40., 5.),
+      Field::Gap => (0., 20., 1.),
+    }
+  }
+
+  fn colour(self) -> Color {
+    match self {
+      Field::Speed => Color::BLUE,
+      Field::Accel => Color::YELLOW,
+      Field::Brake => Color::RED,
+      Field::Cars => Color::GREEN,
+      Field::CarLen => Color::ORANGE,
+      Field::Gap => Color::PURPLE,
+    }
+  }
+
+  fn get(self, consist: &Consist) -> f32 {
+    match self {
+      Field::Speed => consist.speed,
+      Field::Accel => consist.accel,
+      Field::Brake => consist.brake,
+      Field::Cars => consist.cars as f32,
+      Field::CarLen => consist.car_len,
+      Field::Gap => consist.gap,
+    }
+  }
+
+  fn set(self, consist: &mut Consist, value: f32) {
+    let (min, max, _) = self.range();
+    let value = value.max(min).min(max);
+
+    match self {
+      Field::Speed => consist.speed = value,
+      Field::Accel => consist.accel = value,
+      Field::Brake => consist.brake = value,
+      Field::Cars => consist.cars = value as usize,
+      Field::CarLen => consist.car_len = value,
+      Field::Gap => consist.gap = value,
+    }
+  }
+}
+
+// the minus button, the bar and the plus button of a row
+fn row(i: usize) -> (Rectangle, Rectangle, Rectangle) {
+  let (x, y) = PANEL_POS;
+  let y = y + PADDING + i as f32 * ROW_HEIGHT;
+  let x = x + PADDING;
+
+  (
+    Rectangle::new((x, y), (BUTTON_SIZE, BUTTON_SIZE)),
+    Rectangle::new((x + BUTTON_SIZE + PADDING, y), (BAR_WIDTH, BUTTON_SIZE)),
+    Rectangle::new((x + BUTTON_SIZE + BAR_WIDTH + PADDING * 2., y), (BUTTON_SIZE, BUTTON_SIZE)),
+  )
+}
+
+fn area() -> Rectangle {
+  let width = BUTTON_SIZE * 2. + BAR_WIDTH + PADDING * 4.;
+  // the last row is the preview
+  let height = ROW_HEIGHT * (FIELDS.len() + 1) as f32 + PADDING;
+
+  Rectangle::new(PANEL_POS, (width, height))
+}
+
+/// Panel for picking what the next spawned train looks like, it lives in screen space
+pub struct SpawnPanel {
+  pub visible: bool,
+}
+
+impl SpawnPanel {
+  pub fn new() -> Self {
+    SpawnPanel {
+      visible: false,
+    }
+  }
+
+  pub fn contains(&self, screen_pos: Vector) -> bool {
+    self.visible && area().contains(screen_pos)
+  }
+
+  /// Handles a click at `screen_pos`, clicks next to the buttons do nothing
+  pub fn click(&self, screen_pos: Vector, consist: &mut Consist) {
+    for (i, field) in FIELDS.iter().enumerate() {
+      let (minus, _, plus) = row(i);
+      let (_, _, step) = field.range();
+      let value = field.get(consist);
+
+      if minus.contains(screen_pos) {
+        field.set(consist, value - step);
+      } else if plus.contains(screen_pos) {
+        field.set(consist, value + step);
+      }
+    }
+  }
+
+  /// The window has to be using a view of the whole screen
+  pub fn draw(&self, window: &mut Window, consist: &Consist) {
+    if !self.visible {
+      return;
+    }
+
+    window.draw(&area(), Color::WHITE.with_alpha(0.9));
+
+    for (i, field) in FIELDS.iter().enumerate() {
+      let (minus, bar, plus) = row(i);
+      let (min, max, _) = field.range();
+      let fill = (field.get(consist) - min) / (max - min);
+
+      window.draw(&minus, Color::BLACK.with_alpha(0.3));
+      window.draw(&Rectangle::new(minus.pos + Vector::new(4., 9.), (12., 2.)), Color::BLACK);
+
+      window.draw(&bar, Color::BLACK.with_alpha(0.1));
+      window.draw(&Rectangle::new(bar.pos, (bar.size.x * fill, bar.size.y)), field.colour());
+
+      window.draw(&plus, Color::BLACK.with_alpha(0.3));
+      window.draw(&Rectangle::new(plus.pos + Vector::new(4., 9.), (12., 2.)), Color::BLACK);
+      window.draw(&Rectangle::new(plus.pos + Vector::new(9., 4.), (2., 12.)), Color::BLACK);
+    }
+
+    // the consist to scale, cut off if it doesn't fit
+    let (_, bar, _) = row(FIELDS.len());
+    let mut x = area().pos.x + PADDING;
+    let end = area().pos.x + area().size.x - PADDING;
+
+    for _ in 0..consist.cars {
+      let len = (consist.car_len * PREVIEW_SCALE).min(end - x);
+      if len <= 0. {
+        break;
+      }
+
+      window.draw(&Rectangle::new((x, bar.pos.y + 5.), (len, 10.)), Color::BLACK.with_alpha(0.7));
+      x += (consist.car_len + consist.gap) * PREVIEW_SCALE;
+    }
+  }
+}
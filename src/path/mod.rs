@@ -1,36 +1,60 @@
 pub mod track;
+pub mod obstacles;
 
 use std::{
-  collections::HashMap,
+  cmp::Reverse,
+  collections::{BinaryHeap, HashMap, HashSet},
+  fmt,
   ops::{
     Add,
     Sub,
   },
-};
-
-use quicksilver::{
-  geom::{Circle},
-  graphics::{Color},
-  lifecycle::{Window}
+  str::FromStr,
 };
 
 use super::{
   GRID_CELL_SIZE,
 };
 
-use self::track::{TrackPiece, Track, TURN_LEN, DIAG_LEN, STRT_LEN};
+use self::track::{Track, TrackPiece, TURN_SIZES, STRT_LEN};
+use self::obstacles::Obstacles;
+
+// pixels between two neighbouring lattice points
+pub const LATTICE_STEP: f32 = GRID_CELL_SIZE / 2.;
 
+/// A point on the track lattice, counted in half grid cells so every place track
+/// can start, end or cross is exact. Pixels are only for drawing and the cursor.
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct Pos(pub i32, pub i32);
 
+/// Where in its grid cell a lattice point lies
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Slot {
+  Corner,
+  // middle of the left edge
+  VerticalEdge,
+  // middle of the bottom edge
+  HorizontalEdge,
+  Center,
+}
+
 impl Pos {
+  /// Pixel coordinates of the point
   pub fn to_float(&self) -> (f32, f32) {
-    (self.0 as f32, self.1 as f32)
+    (self.0 as f32 * LATTICE_STEP, self.1 as f32 * LATTICE_STEP)
   }
 
-  pub fn scale(&mut self, scl: f32) {
-    self.0 = (self.0 as f32 * scl) as i32;
-    self.1 = (self.1 as f32 * scl) as i32;
+  pub fn cell(&self) -> (i32, i32) {
+    (self.0 >> 1, self.1 >> 1)
+  }
+
+  pub fn slot(&self) -> Slot {
+    match (self.0 & 1, self.1 & 1) {
+      (0, 0) => Slot::Corner,
+      (0, _) => Slot::VerticalEdge,
+      (_, 0) => Slot::HorizontalEdge,
+      _ => Slot::Center,
+    }
   }
 }
 
@@ -93,21 +117,19 @@ pub enum Dir {
 }
 
 impl Dir {
+  /// One straight or diagonal piece in this direction
   pub fn to_pos(&self) -> Pos {
     use self::Dir::*;
 
-    let diag = (2f32.sqrt() * GRID_CELL_SIZE as f32) as i32;
-    let strt = GRID_CELL_SIZE as i32;
-
     match &self {
-      Up => Pos(0, strt),
-      UpRight => Pos(diag, diag),
-      Right => Pos(strt, 0),
-      DownRight => Pos(diag, -diag),
-      Down => Pos(0, -strt),
-      DownLeft => Pos(-diag, -diag),
-      Left => Pos(-strt, 0),
-      UpLeft => Pos(-diag, diag),
+      Up => Pos(0, 2),
+      UpRight => Pos(1, 1),
+      Right => Pos(2, 0),
+      DownRight => Pos(1, -1),
+      Down => Pos(0, -2),
+      DownLeft => Pos(-1, -1),
+      Left => Pos(-2, 0),
+      UpLeft => Pos(-1, 1),
     }
   }
 
@@ -153,12 +175,26 @@ impl Dir {
       Right => 90.0,
       DownRight => 135.0,
       Down => 180.0,
-      DownLeft => 235.0,
+      DownLeft => 225.0,
       Left => 270.0,
       UpLeft => 315.0,
     }
   }
 
+  /// Turns clockwise by `steps` eighths of a circle, counter-clockwise if negative
+  pub fn rotate(&self, steps: i32) -> Dir {
+    Dir::from_angle((((self.into_angle() as i32 + steps * 45) % 360) + 360) as f32 % 360.)
+  }
+
+  pub fn is_diagonal(&self) -> bool {
+    use self::Dir::*;
+
+    match self {
+      UpRight | DownRight | DownLeft | UpLeft => true,
+      Up | Right | Down | Left => false,
+    }
+  }
+
   pub fn difference(&self, other: Dir) -> f32 {
     let self_angle = self.into_angle();
     let other_angle = other.into_angle();
@@ -171,6 +207,32 @@ impl Dir {
   }
 }
 
+impl fmt::Display for Dir {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt::Debug::fmt(self, f)
+  }
+}
+
+impl FromStr for Dir {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Dir, String> {
+    use self::Dir::*;
+
+    Ok(match s {
+      "Up" => Up,
+      "UpRight" => UpRight,
+      "Right" => Right,
+      "DownRight" => DownRight,
+      "Down" => Down,
+      "DownLeft" => DownLeft,
+      "Left" => Left,
+      "UpLeft" => UpLeft,
+      _ => return Err(format!("invalid direction {:?}", s)),
+    })
+  }
+}
+
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct Connection {
   pub pos: Pos,
@@ -185,91 +247,205 @@ impl Connection {
     }
   }
 
-  fn gen_connections(&self) -> Vec<(Connection, i32)> {
+  /// Whether track in this direction can run through the position: straights run
+  /// through the middle of cells, diagonals from one edge midpoint to the next
+  fn on_lattice(&self) -> bool {
+    use self::Dir::*;
+
+    match (self.dir, self.pos.slot()) {
+      (Right, Slot::VerticalEdge) | (Left, Slot::VerticalEdge) => true,
+      (Up, Slot::HorizontalEdge) | (Down, Slot::HorizontalEdge) => true,
+      (UpRight, slot) | (DownRight, slot) | (DownLeft, slot) | (UpLeft, slot) => {
+        slot == Slot::VerticalEdge || slot == Slot::HorizontalEdge
+      }
+      _ => false,
+    }
+  }
+
+  /// The connection one straight or diagonal piece further ahead
+  fn next_run(&self) -> Connection {
+    Connection::new(self.pos + self.dir.to_pos(), self.dir)
+  }
+
+  /// Every connection a single piece can reach, the straight or diagonal
+  /// continuation first, then turns of every size and lane shifts
+  fn gen_connections(&self) -> Vec<Connection> {
     let start = *self;
+    let mut conns = vec![start.next_run()];
 
-    let gs = GRID_CELL_SIZE as f32;
-    let (x, y) = start.pos.to_float();
+    for &side in [-1, 1].iter() {
+      let turned = start.dir.rotate(side);
 
-    let is_x = x % gs == 0.;
+      // a turn runs 3 half cells along the straight direction for every one to the side
+      let (ahead, aside) = if start.dir.is_diagonal() {
+        (turned, turned.rotate(-2 * side))
+      } else {
+        (start.dir, start.dir.rotate(2 * side))
+      };
 
-    use self::Dir::*;
+      for &size in TURN_SIZES.iter() {
+        let pos = start.pos + half_cells(ahead, 3 * size) + half_cells(aside, size);
+        conns.push(Connection::new(pos, turned));
+      }
 
-    let conn = |pos: (f32, f32), dir: Dir| {
-      Connection::new(Pos(pos.0 as i32, pos.1 as i32), dir)
+      // over to the neighbouring line
+      let shift = if start.dir.is_diagonal() {
+        half_cells(start.dir, 4) + half_cells(start.dir.rotate(2 * side), 1)
+      } else {
+        half_cells(start.dir, 8) + half_cells(start.dir.rotate(2 * side), 2)
+      };
+      conns.push(Connection::new(start.pos + shift, start.dir));
+    }
+
+    conns.retain(|conn| conn.on_lattice());
+    conns
+  }
+}
+
+// `n` half cells in `dir`, diagonals go half a cell along both axes per step
+fn half_cells(dir: Dir, n: i32) -> Pos {
+  let Pos(x, y) = dir.to_pos();
+
+  Pos(x.signum() * n, y.signum() * n)
+}
+
+const DEBUG: bool = true;
+
+// most nodes a single search expands before giving up
+pub const NODE_BUDGET: usize = 20000;
+
+/// What the path search optimises for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PathStyle {
+  Shortest,
+  FewestTurns,
+  PreferDiagonals,
+}
+
+impl PathStyle {
+  pub fn next(self) -> PathStyle {
+    use self::PathStyle::*;
+
+    match self {
+      Shortest => FewestTurns,
+      FewestTurns => PreferDiagonals,
+      PreferDiagonals => Shortest,
+    }
+  }
+
+  fn costs(self) -> Costs {
+    use self::PathStyle::*;
+
+    // every factor stays at least 1 so the heuristic below stays admissible
+    let (straight, diagonal, turn_penalty) = match self {
+      // a tiny penalty so equally long paths don't zig-zag
+      Shortest => (1., 1., 1),
+      FewestTurns => (1., 1., STRT_LEN as i32 * 40),
+      PreferDiagonals => (1.5, 1., 1),
     };
 
-    let turn = TURN_LEN as i32;
-    let strt = STRT_LEN as i32;
-    let diag = DIAG_LEN as i32;
-
-    let conns = match start.dir {
-      Right => vec![
-        (conn((x + 1.5 * gs, y - 0.5 * gs), DownRight), turn),
-        (conn((x + 1. * gs, y), Right), strt),
-        (conn((x + 1.5 * gs, y + 0.5 * gs), UpRight), turn),
-      ],
-      UpRight => vec![
-        (conn((x + 0.5 * gs, y + 0.5 * gs), UpRight), diag),
-        if is_x {
-          (conn((x + 0.5 * gs, y + 1.5 * gs), Up), turn)
-        } else {
-          (conn((x + 1.5 * gs, y + 0.5 * gs), Right), turn)
-        },
-      ],
-      DownRight => vec![
-        (conn((x + 0.5 * gs, y - 0.5 * gs), DownRight), diag),
-        if is_x {
-          (conn((x + 0.5 * gs, y - 1.5 * gs), Down), turn)
-        } else {
-          (conn((x + 1.5 * gs, y - 0.5 * gs), Right), turn)
-        },
-      ],
-      Up => vec![
-        (conn((x + 0.5 * gs, y + 1.5 * gs), UpRight), turn),
-        (conn((x, y + 1. * gs), Up), strt),
-        (conn((x - 0.5 * gs, y + 1.5 * gs), UpLeft), turn),
-      ],
-      Down => vec![
-        (conn((x - 0.5 * gs, y - 1.5 * gs), DownLeft), turn),
-        (conn((x, y - 1. * gs), Down), strt),
-        (conn((x + 0.5 * gs, y - 1.5 * gs), DownRight), turn),
-      ],
-      Left => vec![
-        (conn((x - 1.5 * gs, y + 0.5 * gs), UpLeft), turn),
-        (conn((x - 1. * gs, y), Left), strt),
-        (conn((x - 1.5 * gs, y - 0.5 * gs), DownLeft), turn),
-      ],
-      UpLeft => vec![
-        (conn((x - 0.5 * gs, y + 0.5 * gs), UpLeft), diag),
-        if is_x {
-          (conn((x - 0.5 * gs, y + 1.5 * gs), Up), turn)
-        } else {
-          (conn((x - 1.5 * gs, y + 0.5 * gs), Left), turn)
-        },
-      ],
-      DownLeft => vec![
-        (conn((x - 0.5 * gs, y - 0.5 * gs), DownLeft), diag),
-        if is_x {
-          (conn((x - 0.5 * gs, y - 1.5 * gs), Down), turn)
-        } else {
-          (conn((x - 1.5 * gs, y - 0.5 * gs), Left), turn)
-        },
-      ],
+    let mut costs = Costs {
+      straight,
+      diagonal,
+      turn_penalty,
+      rate: 0.,
+      turn_extra: 0.,
     };
 
-    conns
+    // every kind of piece, straights and diagonals look the same in all directions
+    let starts = [Connection::new(Pos(0, 1), Dir::Right), Connection::new(Pos(0, 1), Dir::UpRight)];
+    let pieces = starts
+        .iter()
+        .flat_map(|&start| start.gen_connections().into_iter().map(move |end| Track::from((start, end))))
+        .collect::<Vec<Track>>();
+
+    let dist = |piece: &Track| {
+      let (dx, dy) = (piece.end().pos - piece.start().pos).to_float();
+      (dx.powi(2) + dy.powi(2)).sqrt()
+    };
+
+    costs.rate = pieces.iter().map(|piece| costs.cost(piece) as f32 / dist(piece)).fold(f32::MAX, f32::min);
+    costs.turn_extra = pieces
+        .iter()
+        .filter(|piece| piece.start().dir != piece.end().dir)
+        .map(|piece| costs.cost(piece) as f32 - costs.rate * dist(piece))
+        .fold(f32::MAX, f32::min);
+
+    costs
   }
 }
 
-const DEBUG: bool = true;
+impl fmt::Display for PathStyle {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      PathStyle::Shortest => write!(f, "shortest"),
+      PathStyle::FewestTurns => write!(f, "fewest turns"),
+      PathStyle::PreferDiagonals => write!(f, "prefer diagonals"),
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Costs {
+  straight: f32,
+  diagonal: f32,
+  turn_penalty: i32,
+  // lowest cost per unit of distance of any piece
+  rate: f32,
+  // least a turn costs on top of the distance it covers
+  turn_extra: f32,
+}
+
+impl Costs {
+  fn cost(&self, piece: &Track) -> i32 {
+    let base = (piece.len() * 10.) as i32;
+    let (from, to) = (piece.start().dir, piece.end().dir);
+
+    match piece {
+      // shifting lanes takes two turns
+      Track::SCurve(_) => base + self.turn_penalty * 2 + SHIFT_PENALTY,
+      _ if from != to => base + self.turn_penalty,
+      _ if to.is_diagonal() => (base as f32 * self.diagonal) as i32,
+      _ => (base as f32 * self.straight) as i32,
+    }
+  }
+}
+
+// lane shifts are for getting onto a parallel line, chained up they'd cut every corner
+const SHIFT_PENALTY: i32 = STRT_LEN as i32 * 10 * 3;
+
+// extra cost of a piece running over existing track, paths rather take a long detour
+// but still show up as an invalid preview when there's no way around
+const BLOCKED_COST: i32 = STRT_LEN as i32 * 10 * 20;
+
+// size the kept search tree can grow to before it gets thrown away
+const TREE_LIMIT: usize = NODE_BUDGET * 10;
+
+/// How much work the last search did
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct SearchStats {
+  // nodes taken off the open list
+  pub expanded: usize,
+  // nodes put on the open list
+  pub generated: usize,
+  // the search ran out of budget before reaching the target
+  pub exhausted: bool,
+}
 
 // grid size, not screen size
 // #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub struct Path {
   start: Connection,
   path: Option<Vec<Track>>,
+  // the found path doesn't run over existing track
+  valid: bool,
+  obstacles: Obstacles,
   debug: Vec<Track>,
+  budget: usize,
+  stats: SearchStats,
+  style: PathStyle,
+  costs: Costs,
+  search: Search,
 }
 
 impl Path {
@@ -277,168 +453,300 @@ impl Path {
     Path {
       start: Connection::new(start, dir),
       path: None,
+      valid: false,
+      obstacles: Obstacles::default(),
       debug: Vec::new(),
+      budget: NODE_BUDGET,
+      stats: SearchStats::default(),
+      style: PathStyle::Shortest,
+      costs: PathStyle::Shortest.costs(),
+      search: Search::default(),
     }
   }
 
+  /// The pieces to build, `None` if there's no path or it runs over existing track
   pub fn into_pieces(self) -> Option<Vec<Track>> {
-    self.path
+    if self.valid { self.path } else { None }
   }
 
-  pub fn draw(&self, window: &mut Window) {
-    // draw path
-//    graphics::set_color(window, [0.0, 0.7, 0.2, 1.0].into())?;
-
-    if DEBUG {
-      for track in self.debug.iter() {
-        track.draw(window, Color::PURPLE);
-      }
-    }
+  pub fn is_valid(&self) -> bool {
+    self.valid
+  }
 
-    if let Some(ref path) = self.path {
-      for track in path.iter() {
-        track.draw(window, Color::CYAN);
-      }
-    }
+  /// Track the path has to keep clear of, throws away the search tree
+  pub fn set_obstacles(&mut self, obstacles: Obstacles) {
+    self.obstacles = obstacles;
+    self.search = Search::default();
+    self.debug.clear();
+  }
 
-    // current pos
-//    graphics::set_color(window, [1.0, 0.0, 0.0, 1.0].into())?;
-    let pos = self.start.pos;
+  pub fn start(&self) -> Connection {
+    self.start
+  }
 
-    window.draw(&Circle::new((pos.0, pos.1), 4), Color::RED);
+  pub fn pieces(&self) -> Option<&Vec<Track>> {
+    self.path.as_ref()
+  }
 
-//    graphics::circle(window, DrawMode::Fill, pos.into(), 4., 0.2)?;
+  /// Every piece the last search looked at, empty unless `DEBUG` is set
+  pub fn debug(&self) -> &[Track] {
+    &self.debug
   }
 
-  fn estimate(from: &Connection, to: &Pos) -> i32 {
-    let dx = (from.pos.0 - to.0) as f32;
-    let dy = (from.pos.1 - to.1) as f32;
-    let dy = if dy == 0.0 { 0.00001 } else { dy };
+  pub fn stats(&self) -> SearchStats {
+    self.stats
+  }
 
-    // FIXME: fuggered
-    let dir = Dir::from_angle(180. * (dx / -dy).atan() / std::f32::consts::PI);
+  pub fn style(&self) -> PathStyle {
+    self.style
+  }
 
-    let pos_diff = (dx.abs() + dy.abs()) * 10.0;
-    let pos_diff_abs = (dx.abs().powi(2) + dy.abs().powi(2)).sqrt() * 11.;
-    let dir_diff = from.dir.difference(dir) * 0.0;
+  /// Changing the style throws away the search tree
+  pub fn set_style(&mut self, style: PathStyle) {
+    if self.style != style {
+      self.style = style;
+      self.costs = style.costs();
+      self.search = Search::default();
+      self.debug.clear();
+    }
+  }
 
-//    (pos_diff + dir_diff) as i32
-    pos_diff_abs as i32
+  /// Limits how many nodes a search may expand
+  pub fn set_budget(&mut self, budget: usize) {
+    self.budget = budget;
   }
 
-  pub fn add_path(&mut self, to: Pos) {
-    if DEBUG {
-      self.debug.clear();
+  /// Lower bound for the cost from `from` to `to`: the straight line distance at the
+  /// cheapest rate plus the turns needed to face the target. With k turns every piece
+  /// heads at most k * 45 degrees off the start direction, so the target can't be
+  /// further off than that either.
+  fn estimate(costs: &Costs, from: &Connection, to: &Pos) -> i32 {
+    let (dx, dy) = (*to - from.pos).to_float();
+    let dist = (dx.powi(2) + dy.powi(2)).sqrt();
+
+    if dist == 0. {
+      return 0;
     }
 
+    // clockwise from up, like `Dir::into_angle`
+    let angle = dx.atan2(dy).to_degrees();
+    let off = (angle - from.dir.into_angle()).abs() % 360.;
+    let off = if off > 180. { 360. - off } else { off };
+
+    // a little slack for targets right on the edge of what k turns can reach
+    let turns = (off / 45. - 0.01).ceil().max(0.);
+
+    (costs.rate * dist + turns * costs.turn_extra) as i32
+  }
+
+  pub fn add_path(&mut self, to: Pos) {
     let path = self.find_path(to);
 
-    self.path = match path {
-      Some(path) => {
-        Some(path.windows(2).map(|c| Track::from((c[0], c[1]))).collect::<Vec<Track>>())
+    self.path = path.map(|path| path.windows(2).map(|c| Track::from((c[0], c[1]))).collect::<Vec<Track>>());
+
+    let obstacles = &self.obstacles;
+    self.valid = self.path.as_ref().is_some_and(|pieces| !pieces.iter().any(|piece| obstacles.blocks(piece)));
+
+    if let Some(pieces) = self.path.as_mut() {
+      for piece in pieces.iter_mut() {
+        if obstacles.crosses(piece) {
+          *piece = Track::crossing(piece.start(), piece.end()).expect("only runs can cross");
+        }
       }
-      None => None,
-    };
+    }
   }
 
+  /// A* from the start to `to`. The start never changes, so the search tree is
+  /// kept between calls and only the open list gets re-scored for a new target.
+  /// Ending on existing track the path has to arrive in a direction that joins it.
   pub fn find_path(&mut self, to: Pos) -> Option<Vec<Connection>> {
-    let mut open: Vec<usize> = Vec::new();
-    let mut closed: Vec<usize> = Vec::new();
+    self.stats = SearchStats::default();
+    // only what this search looks at gets shown, the whole tree is too much to draw every frame
+    self.debug.clear();
 
-    let mut nodes: Vec<Node> = Vec::new();
+    if self.search.nodes.len() > TREE_LIMIT {
+      self.search = Search::default();
+    }
 
-    let mut lookup: HashMap<Connection, usize> = HashMap::new();
+    let costs = self.costs;
+    let obstacles = &self.obstacles;
+    let search = &mut self.search;
+
+    if search.nodes.is_empty() {
+      let head = self.start;
+
+      let start = Node {
+        conn: head,
+        g_score: 0i32,
+        f_score: Path::estimate(&costs, &head, &to),
+      };
+
+      search.lookup.insert(start.conn, 0);
+      search.open.push(Reverse((start.f_score, 0)));
+      search.nodes.push(start);
+      search.children.push(0);
+      search.target = Some(to);
+      self.stats.generated += 1;
+    }
 
-    let mut children: Vec<usize> = Vec::new();
+    let goal_dirs = obstacles.joining_dirs(to);
+    let is_goal = |conn: &Connection| conn.pos == to && (goal_dirs.is_empty() || goal_dirs.contains(&conn.dir));
+
+    if search.target != Some(to) {
+      let closed = &search.closed;
+      let open = search.open
+          .drain()
+          .map(|Reverse((_, i))| i)
+          .filter(|i| !closed.contains(i))
+          .collect::<HashSet<usize>>();
+
+      for i in open {
+        let node = &mut search.nodes[i];
+        node.f_score = node.g_score + Path::estimate(&costs, &node.conn, &to);
+        search.open.push(Reverse((node.f_score, i)));
+      }
 
-    let head = self.start;
+      search.target = Some(to);
+    }
 
-    let start = Node {
-      conn: head,
-      g_score: 0i32,
-      f_score: Path::estimate(&head, &to),
+    let reached = if goal_dirs.is_empty() {
+      search.reached.get(&to).filter(|i| search.closed.contains(i)).cloned()
+    } else {
+      goal_dirs.iter()
+          .filter_map(|dir| search.lookup.get(&Connection::new(to, *dir)))
+          .filter(|i| search.closed.contains(i))
+          .min_by_key(|i| search.nodes[**i].g_score)
+          .cloned()
     };
 
-    let mut count = 0;
+    // the turns in the estimate make it inconsistent, so a goal closed by an earlier
+    // search only has its shortest path once nothing left open could still beat it
+    let settled = |search: &Search, f_score: i32| reached
+        .filter(|i| search.closed.contains(i) && search.nodes[*i].g_score <= f_score);
 
-    lookup.insert(start.conn, count);
-    open.push(count);
-    nodes.push(start);
-    children.push(0);
-    count += 1;
+    // lowest f-score first, stale entries are skipped when popped
+    while let Some(Reverse((f_score, target))) = search.open.pop() {
+      let node = search.nodes[target];
 
-    while open.len() > 0 {
-      let target: usize = open.iter().fold((0, i32::max_value()), |acc, i| {
-        let node = nodes.get(*i).expect("nodes in the open list exist");
-        if node.f_score < acc.1 { (*i, node.f_score) } else { acc }
-      }).0;
-
-      let node = nodes[target];
+      if f_score != node.f_score || search.closed.contains(&target) {
+        continue;
+      }
 
-      if node.conn.pos == to {
-        let mut target = target;
+      let done = settled(search, f_score);
+      if done.is_some() || is_goal(&node.conn) || self.stats.expanded >= self.budget {
+        // stays open so the next search can pick it up again
+        search.open.push(Reverse((f_score, target)));
 
-        let mut total = Vec::new();
+        if let Some(i) = done {
+          return Some(search.trace(i));
+        }
 
-        while target != 0 {
-          total.push(target);
-          target = children[target];
+        if is_goal(&node.conn) {
+          return Some(search.trace(target));
         }
 
-        total.push(target);
+        self.stats.exhausted = true;
+        return None;
+      }
+
+      self.stats.expanded += 1;
+      search.closed.insert(target);
 
-        total.reverse();
+      let best = search.reached.entry(node.conn.pos).or_insert(target);
+      if search.nodes[*best].g_score > node.g_score {
+        *best = target;
+      }
 
-        return Some(
-          total
-              .iter()
-              .map(|i| nodes.get(*i).expect("all nodes in the children list should exist").conn)
-              .collect()
-        );
+      let mut next = node.conn.gen_connections();
+      // runs two pieces long can be laid over existing track as a crossing
+      let two = node.conn.next_run().next_run();
+      if obstacles.crosses(&Track::from((node.conn, two))) {
+        next.push(two);
       }
 
-      open.remove_item(&target);
-      closed.push(target);
+      for conn in next {
+        let piece = Track::from((node.conn, conn));
+        let mut total_g = node.g_score + costs.cost(&piece);
 
-      for (conn, len) in node.conn.gen_connections() {
-        let total_g = node.g_score + len * 10;
+        if obstacles.blocks(&piece) {
+          total_g += BLOCKED_COST;
+        }
 
-        if let Some(i) = lookup.get(&conn) {
-          if !closed.contains(i) {
-            let mut n_node = nodes.get_mut(*i).expect("nodes should exists if they are in lookup");
+        if let Some(i) = search.lookup.get(&conn) {
+          let n_node = search.nodes.get_mut(*i).expect("nodes should exists if they are in lookup");
 
-            if n_node.g_score <= total_g {
-              continue;
-            }
+          if n_node.g_score <= total_g {
+            continue;
+          }
 
-            let child = children.get_mut(*i).expect("a children entry should exist for all nodes");
-            *child = target;
+          let child = search.children.get_mut(*i).expect("a children entry should exist for all nodes");
+          *child = target;
 
-            n_node.g_score = total_g;
-            n_node.f_score = total_g + Path::estimate(&n_node.conn, &to)
-          }
+          n_node.g_score = total_g;
+          n_node.f_score = total_g + Path::estimate(&costs, &n_node.conn, &to);
+          // closed nodes found a cheaper way get looked at again
+          search.closed.remove(i);
+          search.open.push(Reverse((n_node.f_score, *i)));
           continue;
         }
 
         let n_node = Node {
           conn,
           g_score: total_g,
-          f_score: total_g + Path::estimate(&conn, &to),
+          f_score: total_g + Path::estimate(&costs, &conn, &to),
         };
 
         if DEBUG {
-          self.debug.push(Track::from((node.conn, conn)));
+          self.debug.push(piece);
         }
 
-        lookup.insert(conn, count);
-        open.push(count);
-        nodes.push(n_node);
-        children.push(target);
-        count += 1;
+        let i = search.nodes.len();
+        search.lookup.insert(conn, i);
+        search.open.push(Reverse((n_node.f_score, i)));
+        search.nodes.push(n_node);
+        search.children.push(target);
+        self.stats.generated += 1;
       }
     }
 
-    None
+    settled(search, i32::MAX).map(|i| search.trace(i))
+  }
+}
+
+/// Search tree kept alive for the whole life of a `Path`
+#[derive(Default)]
+struct Search {
+  open: BinaryHeap<Reverse<(i32, usize)>>,
+  closed: HashSet<usize>,
+  nodes: Vec<Node>,
+  lookup: HashMap<Connection, usize>,
+  children: Vec<usize>,
+  // cheapest closed node at each position
+  reached: HashMap<Pos, usize>,
+  // position the open list is scored for
+  target: Option<Pos>,
+}
+
+impl Search {
+  // connections from the start to the node
+  fn trace(&self, target: usize) -> Vec<Connection> {
+    let mut target = target;
+
+    let mut total = Vec::new();
+
+    while target != 0 {
+      total.push(target);
+      target = self.children[target];
+    }
+
+    total.push(target);
+
+    total.reverse();
+
+    total
+        .iter()
+        .map(|i| self.nodes.get(*i).expect("all nodes in the children list should exist").conn)
+        .collect()
   }
 }
 
@@ -448,3 +756,68 @@ struct Node {
   g_score: i32,
   f_score: i32,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::{Rng, SeedableRng};
+  use rand_chacha::ChaCha20Rng;
+
+  // spots all over the map, the cursor jumping between them
+  fn targets() -> Vec<Pos> {
+    let mut rng = ChaCha20Rng::seed_from_u64(63);
+
+    (0..30)
+        .map(|_| {
+          let x = rng.gen_range(-20, 21);
+          // edge midpoints have one odd coordinate
+          let y = rng.gen_range(-10, 10) * 2 + (x + 1) % 2;
+          Pos(x, y)
+        })
+        .collect()
+  }
+
+  fn path(style: PathStyle) -> Path {
+    let mut path = Path::new(Pos(0, 1), Dir::Right);
+    path.set_style(style);
+    path
+  }
+
+  fn cost(path: &Path) -> Option<i32> {
+    path.pieces().map(|pieces| pieces.iter().map(|piece| path.costs.cost(piece)).sum())
+  }
+
+  #[test]
+  fn reused_tree_finds_the_cheapest_paths() {
+    for &style in &[PathStyle::Shortest, PathStyle::FewestTurns, PathStyle::PreferDiagonals] {
+      let mut reused = path(style);
+
+      for &to in targets().iter() {
+        reused.add_path(to);
+
+        let mut fresh = path(style);
+        fresh.add_path(to);
+
+        assert!(cost(&fresh).is_some(), "no path to {:?}", to);
+        assert_eq!(cost(&reused), cost(&fresh), "{} path to {:?}", style, to);
+      }
+    }
+  }
+
+  #[test]
+  fn turns_in_the_estimate_keep_paths_cheapest() {
+    for &style in &[PathStyle::Shortest, PathStyle::FewestTurns, PathStyle::PreferDiagonals] {
+      for &to in targets().iter() {
+        let mut aware = path(style);
+        aware.add_path(to);
+
+        // without the extra cost of turns the estimate only goes by distance
+        let mut plain = path(style);
+        plain.costs.turn_extra = 0.;
+        plain.add_path(to);
+
+        assert_eq!(cost(&aware), cost(&plain), "{} path to {:?}", style, to);
+      }
+    }
+  }
+}
@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+  GRID_CELL_SIZE,
+  Connection,
+  Dir,
+  Pos,
+  track::{Track, TrackPiece},
+};
+
+// closest two pieces of track may get without touching
+const CLEARANCE: f32 = 4.;
+// distance between the points pieces get sampled at
+const SAMPLE_STEP: f32 = 2.;
+const CELL_SIZE: f32 = GRID_CELL_SIZE / 2.;
+
+// sampled points of existing track by grid cell, with the track they belong to
+type CellMap = HashMap<(i32, i32), Vec<((f32, f32), usize)>>;
+
+fn cell((x, y): (f32, f32)) -> (i32, i32) {
+  ((x / CELL_SIZE).floor() as i32, (y / CELL_SIZE).floor() as i32)
+}
+
+fn samples(track: &Track) -> Vec<(f32, f32)> {
+  let steps = (track.len() / SAMPLE_STEP).ceil().max(1.) as i32;
+
+  (0..steps + 1)
+      .map(|i| track.lerp(i as f32 / steps as f32))
+      .collect()
+}
+
+/// Existing track new paths must not run over
+#[derive(Default)]
+pub struct Obstacles {
+  cells: CellMap,
+  tracks: Vec<Track>,
+}
+
+impl Obstacles {
+  pub fn new(tracks: &[Track]) -> Self {
+    let mut obstacles = Obstacles::default();
+
+    for (i, track) in tracks.iter().enumerate() {
+      for point in samples(track) {
+        obstacles.cells.entry(cell(point)).or_insert(Vec::new()).push((point, i));
+      }
+    }
+
+    obstacles.tracks = tracks.to_vec();
+
+    obstacles
+  }
+
+  /// Directions a path can arrive at `pos` in to run straight onto existing track,
+  /// in the middle of a straight run either way works as the run gets split there
+  pub fn joining_dirs(&self, pos: Pos) -> Vec<Dir> {
+    let mut dirs = Vec::new();
+
+    for track in self.tracks.iter() {
+      let (start, end) = (track.start(), track.end());
+
+      if start.pos == pos {
+        dirs.push(start.dir);
+      }
+      if end.pos == pos {
+        dirs.push(end.dir.opposite());
+      }
+      if track.runs_through(pos) {
+        dirs.push(start.dir);
+        dirs.push(start.dir.opposite());
+      }
+    }
+
+    dirs
+  }
+
+  /// Whether `piece` would overlap or cross existing track. Pieces joined at a
+  /// connection or branching off a straight run are allowed to touch, that's how
+  /// junctions and continuations look, but not to lie right on top of each other.
+  /// Runs crossing other runs right in their middle are fine too, they become crossings.
+  pub fn blocks(&self, piece: &Track) -> bool {
+    let hit = self.collisions(piece);
+    !hit.is_empty() && !self.crosses_all(piece, &hit)
+  }
+
+  /// Whether `piece` has to be laid as a crossing over existing track
+  pub fn crosses(&self, piece: &Track) -> bool {
+    let hit = self.collisions(piece);
+    !hit.is_empty() && self.crosses_all(piece, &hit)
+  }
+
+  // every track in `hit` is a straight or diagonal run crossing `piece` at an angle
+  // at the center of `piece`, on the lattice so the crossing lines up with the grid
+  fn crosses_all(&self, piece: &Track, hit: &HashSet<usize>) -> bool {
+    match piece {
+      Track::Strt(_) | Track::Diag(_) | Track::Cross(_) => {}
+      Track::Turn(_) | Track::SCurve(_) => return false,
+    }
+
+    let (start, end) = (piece.start(), piece.end());
+    let Pos(dx, dy) = end.pos - start.pos;
+    if dx % 2 != 0 || dy % 2 != 0 {
+      return false;
+    }
+    let center = start.pos + Pos(dx / 2, dy / 2);
+
+    hit.iter().all(|&i| {
+      let track = &self.tracks[i];
+      let (s, e) = (track.start(), track.end());
+
+      match track {
+        Track::Strt(_) | Track::Diag(_) => {}
+        Track::Turn(_) | Track::SCurve(_) | Track::Cross(_) => return false,
+      }
+
+      s.dir != start.dir && s.dir != start.dir.opposite()
+          && (s.pos == center || e.pos == center || track.runs_through(center))
+    })
+  }
+
+  // existing tracks `piece` touches in a way that isn't a plain join
+  fn collisions(&self, piece: &Track) -> HashSet<usize> {
+    let mut hit = HashSet::new();
+
+    if self.cells.is_empty() {
+      return hit;
+    }
+
+    let (start, end) = (piece.start(), piece.end());
+    // running the same or the opposite way
+    let parallel = |a: Connection, b: Connection| a.dir == b.dir || a.dir == b.dir.opposite();
+    let joined = |track: &Track, conn: Connection| {
+      let (s, e) = (track.start(), track.end());
+      (s.pos == conn.pos && parallel(s, conn)) || (e.pos == conn.pos && parallel(e, conn)) || (track.runs_through(conn.pos) && parallel(s, conn))
+    };
+    let on = |track: &Track, pos: Pos| track.start().pos == pos || track.end().pos == pos || track.runs_through(pos);
+    let points = samples(piece);
+
+    // most pieces are nowhere near existing track
+    let (min, max) = points.iter().fold(((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)), |(min, max), &(x, y)| {
+      ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+    });
+    let (min, max) = (cell((min.0 - CLEARANCE, min.1 - CLEARANCE)), cell((max.0 + CLEARANCE, max.1 + CLEARANCE)));
+
+    let near = (min.0..max.0 + 1).any(|cx| (min.1..max.1 + 1).any(|cy| self.cells.contains_key(&(cx, cy))));
+    if !near {
+      return hit;
+    }
+
+    for (x, y) in points {
+      let (cx, cy) = cell((x, y));
+
+      for ox in -1..2 {
+        for oy in -1..2 {
+          let near = match self.cells.get(&(cx + ox, cy + oy)) {
+            Some(near) => near,
+            None => continue,
+          };
+
+          for &((px, py), i) in near {
+            if hit.contains(&i) || (px - x).powi(2) + (py - y).powi(2) > CLEARANCE.powi(2) {
+              continue;
+            }
+
+            let track = &self.tracks[i];
+            let shares_end = joined(track, start) || joined(track, end);
+            // both ends on the same track means they run on top of each other
+            let overlaps = on(track, start.pos) && on(track, end.pos);
+
+            if !shares_end || overlaps {
+              hit.insert(i);
+            }
+          }
+        }
+      }
+    }
+
+    hit
+  }
+}
@@ -1,17 +1,12 @@
-use std::f32::consts::PI;
-
-use quicksilver::{
-  graphics::{Color},
-  lifecycle::{Window}
-};
-
-use super::super::draw_line;
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_4};
 
 use path::{Connection, Pos, Dir};
 use super::GRID_CELL_SIZE;
 
-pub const STRT_LEN: f32 = GRID_CELL_SIZE as f32;
+pub const STRT_LEN: f32 = GRID_CELL_SIZE;
 
+// pieces always have some length, there's no empty piece to ask about
+#[allow(clippy::len_without_is_empty)]
 pub trait TrackPiece {
   fn start(&self) -> Connection;
   fn end(&self) -> Connection;
@@ -20,20 +15,43 @@ pub trait TrackPiece {
     STRT_LEN
   }
 
-  fn lerp(&self, perc: f32) -> Pos {
-    let start = self.start().pos;
-    let end = self.end().pos;
+  /// Fastest a train may run on the piece, `None` if it's not limited
+  fn speed_limit(&self) -> Option<f32> {
+    None
+  }
+
+  /// The point `perc` of the way along the piece
+  fn lerp(&self, perc: f32) -> (f32, f32) {
+    let (sx, sy) = self.start().pos.to_float();
+    let (ex, ey) = self.end().pos.to_float();
+
+    (sx + (ex - sx) * perc, sy + (ey - sy) * perc)
+  }
+
+  /// Unit vector the piece runs in `perc` of the way along, from start to end
+  fn tangent(&self, _perc: f32) -> (f32, f32) {
+    let (dx, dy) = (self.end().pos - self.start().pos).to_float();
+    let len = (dx.powi(2) + dy.powi(2)).sqrt();
 
-    let mut diff = end - start;
-    diff.scale(perc);
-    start + diff
+    (dx / len, dy / len)
   }
 
-  fn draw(&self, window: &mut Window, color: Color) {
-    let start = self.start().pos;
-    let end = self.end().pos;
+  fn distance(&self, pos: (f32, f32)) -> f32 {
+    self.closest(pos).1
+  }
 
-    draw_line(window, start.0 as f32, start.1 as f32, end.0 as f32, end.1 as f32, 2., color);
+  /// How far along the piece the point closest to `pos` is, and how far away it is
+  fn closest(&self, pos: (f32, f32)) -> (f32, f32) {
+    let (x, y) = pos;
+    let steps = (self.len() / 4.).ceil().max(1.) as i32;
+
+    (0..steps + 1)
+        .map(|i| {
+          let perc = i as f32 / steps as f32;
+          let (px, py) = self.lerp(perc);
+          (perc * self.len(), ((px - x).powi(2) + (py - y).powi(2)).sqrt())
+        })
+        .fold((0., f32::MAX), |a, b| if b.1 < a.1 { b } else { a })
   }
 }
 
@@ -59,6 +77,16 @@ impl TrackPiece for Straight {
   fn end(&self) -> Connection {
     self.end
   }
+
+  // runs can be longer than a grid cell
+  fn len(&self) -> f32 {
+    run_len(self.start.pos, self.end.pos)
+  }
+}
+
+fn run_len(start: Pos, end: Pos) -> f32 {
+  let (dx, dy) = (end - start).to_float();
+  (dx.powi(2) + dy.powi(2)).sqrt()
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
@@ -69,7 +97,7 @@ pub struct Diagonal {
 
 use std::f32::consts::SQRT_2;
 
-pub const DIAG_LEN: f32 = SQRT_2 * 0.5 * GRID_CELL_SIZE as f32;
+pub const DIAG_LEN: f32 = SQRT_2 * 0.5 * GRID_CELL_SIZE;
 
 impl Diagonal {
   pub fn new(start: Connection, end: Connection) -> Self {
@@ -89,75 +117,181 @@ impl TrackPiece for Diagonal {
   }
 
   fn len(&self) -> f32 {
-    DIAG_LEN
+    run_len(self.start.pos, self.end.pos)
   }
 }
 
-const TURN_RADIUS: f32 = 2.5 * GRID_CELL_SIZE as f32;
-const TURN_ANGLE: f32 = 0.65;
-// 0.75_f32.atan();
-pub const TURN_LEN: f32 = (TURN_ANGLE * TURN_RADIUS);
+/// A straight or diagonal run with other track crossing it in the middle. The
+/// routes stay separate for trains, but only one train may be on the crossing.
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct Crossing {
+  start: Connection,
+  end: Connection,
+}
+
+impl Crossing {
+  pub fn new(start: Connection, end: Connection) -> Self {
+    Crossing {
+      start,
+      end,
+    }
+  }
+
+  /// Where the other track crosses
+  pub fn center(&self) -> Pos {
+    let Pos(dx, dy) = self.end.pos - self.start.pos;
+    self.start.pos + Pos(dx / 2, dy / 2)
+  }
+}
+
+impl TrackPiece for Crossing {
+  fn start(&self) -> Connection {
+    self.start
+  }
+  fn end(&self) -> Connection {
+    self.end
+  }
+
+  fn len(&self) -> f32 {
+    run_len(self.start.pos, self.end.pos)
+  }
+}
 
-const TURN_DIVISIONS: i32 = 16;
-const TURN_ANGLE_FRACT: f32 = TURN_ANGLE / TURN_DIVISIONS as f32;
+// sideways acceleration trains may pull in turns, in pixels per second squared
+const MAX_LATERAL_ACCEL: f32 = 200.;
 
+// number of straight lines a turn is drawn with
+pub const TURN_DIVISIONS: i32 = 16;
+
+/// Tight, standard and wide turns, as multiples of the tight one. A turn of size k
+/// moves 3k half cells ahead and k to the side, see `gen_connections`.
+pub const TURN_SIZES: [i32; 3] = [1, 2, 3];
+
+fn unit_vector(dir: Dir) -> (f32, f32) {
+  let (x, y) = dir.to_pos().to_float();
+  let len = (x.powi(2) + y.powi(2)).sqrt();
+  (x / len, y / len)
+}
+
+/// Part of a circle, walked from `base_ang` by `sweep` radians
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+struct Arc {
+  center: (f32, f32),
+  radius: f32,
+  base_ang: f32,
+  sweep: f32,
+}
+
+impl Arc {
+  /// The circle leaving `anchor` in `heading` that runs through `other`, from `anchor`
+  /// to `other`. `None` if `other` is straight ahead or behind.
+  fn new(anchor: (f32, f32), heading: Dir, other: (f32, f32)) -> Option<Arc> {
+    let (hx, hy) = unit_vector(heading);
+    let (dx, dy) = (other.0 - anchor.0, other.1 - anchor.1);
+
+    // how far `other` is off to the left of the heading
+    let side = dx * -hy + dy * hx;
+    if side == 0. || dx * hx + dy * hy <= 0. {
+      return None;
+    }
+
+    let radius = (dx.powi(2) + dy.powi(2)) / (2. * side.abs());
+    // the chord is half as far off the heading as the arc turns
+    let sweep = 2. * side.atan2(dx * hx + dy * hy);
+
+    Some(Arc::leaving(anchor, (hx, hy), radius, sweep))
+  }
+
+  /// The arc leaving `anchor` in the unit vector `heading`, turning left for a positive `sweep`
+  fn leaving(anchor: (f32, f32), (hx, hy): (f32, f32), radius: f32, sweep: f32) -> Arc {
+    let turn = sweep.signum();
+    let center = (anchor.0 - hy * radius * turn, anchor.1 + hx * radius * turn);
+
+    Arc {
+      center,
+      radius,
+      base_ang: (anchor.1 - center.1).atan2(anchor.0 - center.0),
+      sweep,
+    }
+  }
+
+  fn len(&self) -> f32 {
+    self.radius * self.sweep.abs()
+  }
+
+  fn speed_limit(&self) -> f32 {
+    (MAX_LATERAL_ACCEL * self.radius).sqrt()
+  }
+
+  fn at(&self, perc: f32) -> (f32, f32) {
+    let ang = self.base_ang + self.sweep * perc;
+    (self.center.0 + self.radius * ang.cos(), self.center.1 + self.radius * ang.sin())
+  }
+
+  fn tangent(&self, perc: f32) -> (f32, f32) {
+    let ang = self.base_ang + self.sweep * perc;
+    let turn = self.sweep.signum();
+    (-ang.sin() * turn, ang.cos() * turn)
+  }
+}
+
+/// A 45 degree turn between a straight and a diagonal: a short straight lead
+/// and an arc, so it meets both connections without a kink
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub struct Turn {
   start: Connection,
   end: Connection,
-  center: Pos,
-  base_ang: f32,
-  dir: i8,
+  // the straight end and the way into the turn from there
+  from: (f32, f32),
+  heading: (f32, f32),
+  lead: f32,
+  arc: Arc,
+  // built from the end, for turns starting on the diagonal
+  reverse: bool,
 }
 
 impl Turn {
-  pub fn new(start: Connection, end: Connection) -> Self {
-    let prev_pos = start.pos;
-    let curr_pos = end.pos;
-
-    fn matc (pos: Pos, center: Pos, turn: i8, ang: f32, reverse: bool) -> (Pos, i8, f32) {
-      (
-        Pos(
-          (pos.0 as f32 + center.0 as f32 * 2.5) as i32,
-          (pos.1 as f32 + center.1 as f32 * 2.5) as i32
-        ),
-        turn,
-        ang * 2. * PI - if reverse { turn as f32 * TURN_ANGLE } else { 0. }
-      )
+  pub fn new(start: Connection, end: Connection) -> Option<Self> {
+    let (from, heading, to, reverse) = if start.dir.is_diagonal() {
+      (end.pos, end.dir.opposite(), start.pos, true)
+    } else {
+      (start.pos, start.dir, end.pos, false)
     };
 
-    use self::Dir::*;
-
-    let (center, dir, base_ang) = match (&start.dir, &end.dir) {
-      (Up, UpLeft) => matc(prev_pos, Dir::Left.into(), 1, 0.0, false),
-      (Up, UpRight) => matc(prev_pos, Dir::Right.into(), -1, 0.5, false),
-      (UpRight, Up) => matc(curr_pos, Dir::Left.into(), 1, 0.0, true),
-      (UpRight, Right) => matc(curr_pos, Dir::Down.into(), -1, 0.25, true),
-      (Right, UpRight) => matc(prev_pos, Dir::Up.into(), 1, 0.75, false),
-      (Right, DownRight) => matc(prev_pos, Dir::Down.into(), -1, 0.25, false),
-      (DownRight, Right) => matc(curr_pos, Dir::Up.into(), 1, 0.75, true),
-      (DownRight, Down) => matc(curr_pos, Dir::Left.into(), -1, 0.0, true),
-      (Down, DownRight) => matc(prev_pos, Dir::Right.into(), 1, 0.5, false),
-      (Down, DownLeft) => matc(prev_pos, Dir::Left.into(), -1, 0.0, false),
-      (DownLeft, Down) => matc(curr_pos, Dir::Right.into(), 1, 0.5, true),
-      (DownLeft, Left) => matc(curr_pos, Dir::Up.into(), -1, 0.75, true),
-      (Left, DownLeft) => matc(prev_pos, Dir::Down.into(), 1, 0.25, false),
-      (Left, UpLeft) => matc(prev_pos, Dir::Up.into(), -1, 0.75, false),
-      (UpLeft, Left) => matc(curr_pos, Dir::Down.into(), 1, 0.25, true),
-      (UpLeft, Up) => matc(curr_pos, Dir::Right.into(), -1, 0.5, true),
-
-      (a, b) => {
-        unreachable!("invalid turn {:?} to {:?}", a, b);
-      }
-    };
+    let (hx, hy) = unit_vector(heading);
+    let (dx, dy) = (to - from).to_float();
+    let (ahead, side) = (dx * hx + dy * hy, dx * -hy + dy * hx);
+
+    // the arc covers all of the sideways part, the lead makes up the rest ahead
+    let radius = side.abs() / (1. - FRAC_1_SQRT_2);
+    let lead = ahead - radius * FRAC_1_SQRT_2;
+    if side == 0. || lead < 0. {
+      return None;
+    }
+
+    // the far end has to point the way the arc bends
+    let far = if reverse { start.dir.opposite() } else { end.dir };
+    if far != heading.rotate(-side.signum() as i32) {
+      return None;
+    }
 
-    Turn {
+    let from = from.to_float();
+    let arc = Arc::leaving((from.0 + hx * lead, from.1 + hy * lead), (hx, hy), radius, FRAC_PI_4 * side.signum());
+
+    Some(Turn {
       start,
       end,
-      dir,
-      base_ang,
-      center,
-    }
+      from,
+      heading: (hx, hy),
+      lead,
+      arc,
+      reverse,
+    })
+  }
+
+  // how far along the piece from the straight end `perc` is
+  fn straight_dist(&self, perc: f32) -> f32 {
+    if self.reverse { (1. - perc) * self.len() } else { perc * self.len() }
   }
 }
 
@@ -169,32 +303,81 @@ impl TrackPiece for Turn {
     self.end
   }
   fn len(&self) -> f32 {
-    TURN_LEN
+    self.lead + self.arc.len()
   }
-  fn lerp(&self, perc: f32) -> Pos {
-    let Pos(cx, cy) = self.center;
-    let div = TURN_ANGLE * perc;
+  fn speed_limit(&self) -> Option<f32> {
+    Some(self.arc.speed_limit())
+  }
+  fn lerp(&self, perc: f32) -> (f32, f32) {
+    let dist = self.straight_dist(perc);
 
-    Pos(cx + (TURN_RADIUS * (self.base_ang + div * self.dir as f32).cos()) as i32, cy + (TURN_RADIUS * (self.base_ang + div * self.dir as f32).sin()) as i32)
+    if dist < self.lead {
+      (self.from.0 + self.heading.0 * dist, self.from.1 + self.heading.1 * dist)
+    } else {
+      self.arc.at((dist - self.lead) / self.arc.len())
+    }
   }
+  fn tangent(&self, perc: f32) -> (f32, f32) {
+    let dist = self.straight_dist(perc);
 
-  fn draw(&self, window: &mut Window, color: Color) {
-    let Pos(cx, cy) = self.center;
+    let (tx, ty) = if dist < self.lead {
+      self.heading
+    } else {
+      self.arc.tangent((dist - self.lead) / self.arc.len())
+    };
 
-    let mut points: Vec<Pos> = Vec::new();
-    points.push(self.start.pos);
+    if self.reverse { (-tx, -ty) } else { (tx, ty) }
+  }
+}
 
-    let divs = (1..TURN_DIVISIONS).map(|e| e as f32 * TURN_ANGLE_FRACT).collect::<Vec<f32>>();
+/// Two opposite arcs shifting a line sideways onto a parallel one
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct SCurve {
+  start: Connection,
+  end: Connection,
+  // the first half, the second half is the same turned around the middle
+  arc: Arc,
+}
 
-    for div in divs {
-      points.push(Pos(cx + (TURN_RADIUS * (self.base_ang + div * self.dir as f32).cos()) as i32, cy + (TURN_RADIUS * (self.base_ang + div * self.dir as f32).sin()) as i32));
-    }
+impl SCurve {
+  pub fn new(start: Connection, end: Connection) -> Option<Self> {
+    let (sx, sy) = start.pos.to_float();
+    let (ex, ey) = end.pos.to_float();
 
-    points.push(self.end.pos);
-    for win in points.windows(2) {
-      let (prev, curr) = (win[0], win[1]);
-      draw_line(window, prev.0 as f32, prev.1 as f32, curr.0 as f32, curr.1 as f32, 2., color);
+    Some(SCurve {
+      start,
+      end,
+      arc: Arc::new((sx, sy), start.dir, ((sx + ex) / 2., (sy + ey) / 2.))?,
+    })
+  }
+}
+
+impl TrackPiece for SCurve {
+  fn start(&self) -> Connection {
+    self.start
+  }
+  fn end(&self) -> Connection {
+    self.end
+  }
+  fn len(&self) -> f32 {
+    self.arc.len() * 2.
+  }
+  fn speed_limit(&self) -> Option<f32> {
+    Some(self.arc.speed_limit())
+  }
+  fn lerp(&self, perc: f32) -> (f32, f32) {
+    if perc <= 0.5 {
+      return self.arc.at(perc * 2.);
     }
+
+    let (sx, sy) = self.start.pos.to_float();
+    let (ex, ey) = self.end.pos.to_float();
+    let (x, y) = self.arc.at((1. - perc) * 2.);
+    (sx + ex - x, sy + ey - y)
+  }
+  // turning the first half around the middle keeps its tangents
+  fn tangent(&self, perc: f32) -> (f32, f32) {
+    self.arc.tangent(if perc <= 0.5 { perc * 2. } else { (1. - perc) * 2. })
   }
 }
 
@@ -203,40 +386,108 @@ pub enum Track {
   Diag(Diagonal),
   Turn(Turn),
   Strt(Straight),
+  Cross(Crossing),
+  SCurve(SCurve),
+}
+
+impl Track {
+  /// The piece joining the two connections, `None` if they can't be joined
+  pub fn new(start: Connection, end: Connection) -> Option<Track> {
+    if start.dir == end.dir {
+      let (hx, hy) = unit_vector(start.dir);
+      let (dx, dy) = (end.pos - start.pos).to_float();
+
+      if dx * hx + dy * hy <= 0. {
+        return None;
+      }
+
+      // off to the side it's a lane shift
+      return Some(if dx * -hy + dy * hx != 0. {
+        Track::SCurve(SCurve::new(start, end)?)
+      } else if start.dir.is_diagonal() {
+        Track::Diag(Diagonal::new(start, end))
+      } else {
+        Track::Strt(Straight::new(start, end))
+      });
+    }
+
+    if end.dir == start.dir.rotate(1) || end.dir == start.dir.rotate(-1) {
+      return Some(Track::Turn(Turn::new(start, end)?));
+    }
+
+    None
+  }
+}
+
+impl Track {
+  /// The straight or diagonal run between the connections as a crossing
+  pub fn crossing(start: Connection, end: Connection) -> Option<Track> {
+    // the middle has to be on the lattice too
+    let Pos(dx, dy) = end.pos - start.pos;
+    if dx % 2 != 0 || dy % 2 != 0 {
+      return None;
+    }
+
+    match Track::new(start, end)? {
+      Track::Strt(_) | Track::Diag(_) => Some(Track::Cross(Crossing::new(start, end))),
+      _ => None,
+    }
+  }
+
+  /// Whether `pos` lies on a straight or diagonal piece, away from its ends
+  pub fn runs_through(&self, pos: Pos) -> bool {
+    let (start, end) = match self {
+      Track::Strt(t) => (t.start, t.end),
+      Track::Diag(t) => (t.start, t.end),
+      // crossings can't be cut, the other route would end up on one half only
+      Track::Turn(_) | Track::SCurve(_) | Track::Cross(_) => return false,
+    };
+
+    let (dx, dy) = (end.pos - start.pos).to_float();
+    let (px, py) = (pos - start.pos).to_float();
+
+    let along = px * dx + py * dy;
+    px * dy - py * dx == 0. && along > 0. && along < dx.powi(2) + dy.powi(2)
+  }
+
+  /// The two pieces a straight or diagonal piece turns into when cut at `pos`
+  pub fn split(&self, pos: Pos) -> Option<(Track, Track)> {
+    if !self.runs_through(pos) {
+      return None;
+    }
+
+    let (start, end) = (self.start(), self.end());
+    let cut = Connection::new(pos, start.dir);
+
+    Some((Track::from((start, cut)), Track::from((cut, end))))
+  }
+
+  /// The joints between pieces of a straight or diagonal run closest to `pos` on
+  /// either side, not counting `pos` itself or the ends of the run
+  pub fn joints_around(&self, pos: Pos) -> Vec<Pos> {
+    if !self.runs_through(pos) {
+      return Vec::new();
+    }
+
+    let start = self.start();
+    let unit = start.dir.to_pos();
+
+    // how many pieces into the run `pos` is
+    let Pos(px, py) = pos - start.pos;
+    let along = (px * unit.0 + py * unit.1) as f32 / (unit.0.pow(2) + unit.1.pow(2)) as f32;
+    let (before, after) = if along.fract() == 0. { (along - 1., along + 1.) } else { (along.floor(), along.ceil()) };
+
+    [before, after]
+        .iter()
+        .map(|&k| start.pos + Pos(unit.0 * k as i32, unit.1 * k as i32))
+        .filter(|&joint| self.runs_through(joint))
+        .collect()
+  }
 }
 
 impl From<(Connection, Connection)> for Track {
   fn from((start, end): (Connection, Connection)) -> Self {
-    use self::Dir::*;
-
-    match (start.dir, end.dir) {
-      (Up, Up) => Track::Strt(Straight::new(start, end)),
-      (Up, UpLeft) => Track::Turn(Turn::new(start, end)),
-      (Up, UpRight) => Track::Turn(Turn::new(start, end)),
-      (UpRight, UpRight) => Track::Diag(Diagonal::new(start, end)),
-      (UpRight, Up) => Track::Turn(Turn::new(start, end)),
-      (UpRight, Right) => Track::Turn(Turn::new(start, end)),
-      (Right, Right) => Track::Strt(Straight::new(start, end)),
-      (Right, UpRight) => Track::Turn(Turn::new(start, end)),
-      (Right, DownRight) => Track::Turn(Turn::new(start, end)),
-      (DownRight, DownRight) => Track::Diag(Diagonal::new(start, end)),
-      (DownRight, Right) => Track::Turn(Turn::new(start, end)),
-      (DownRight, Down) => Track::Turn(Turn::new(start, end)),
-      (Down, Down) => Track::Strt(Straight::new(start, end)),
-      (Down, DownRight) => Track::Turn(Turn::new(start, end)),
-      (Down, DownLeft) => Track::Turn(Turn::new(start, end)),
-      (DownLeft, DownLeft) => Track::Diag(Diagonal::new(start, end)),
-      (DownLeft, Down) => Track::Turn(Turn::new(start, end)),
-      (DownLeft, Left) => Track::Turn(Turn::new(start, end)),
-      (Left, Left) => Track::Strt(Straight::new(start, end)),
-      (Left, DownLeft) => Track::Turn(Turn::new(start, end)),
-      (Left, UpLeft) => Track::Turn(Turn::new(start, end)),
-      (UpLeft, UpLeft) => Track::Diag(Diagonal::new(start, end)),
-      (UpLeft, Left) => Track::Turn(Turn::new(start, end)),
-      (UpLeft, Up) => Track::Turn(Turn::new(start, end)),
-
-      _ => unreachable!("This is not a valid Track"),
-    }
+    Track::new(start, end).expect("This is not a valid Track")
   }
 }
 
@@ -246,6 +497,8 @@ impl TrackPiece for Track {
       Track::Turn(t) => t.start(),
       Track::Diag(t) => t.start(),
       Track::Strt(t) => t.start(),
+      Track::Cross(t) => t.start(),
+      Track::SCurve(t) => t.start(),
     }
   }
 
@@ -254,6 +507,8 @@ impl TrackPiece for Track {
       Track::Turn(t) => t.end(),
       Track::Diag(t) => t.end(),
       Track::Strt(t) => t.end(),
+      Track::Cross(t) => t.end(),
+      Track::SCurve(t) => t.end(),
     }
   }
 
@@ -262,22 +517,55 @@ impl TrackPiece for Track {
       Track::Turn(t) => t.len(),
       Track::Diag(t) => t.len(),
       Track::Strt(t) => t.len(),
+      Track::Cross(t) => t.len(),
+      Track::SCurve(t) => t.len(),
     }
   }
 
-  fn lerp(&self, perc: f32) -> Pos {
+  fn lerp(&self, perc: f32) -> (f32, f32) {
     match self {
       Track::Turn(t) => t.lerp(perc),
       Track::Diag(t) => t.lerp(perc),
       Track::Strt(t) => t.lerp(perc),
+      Track::Cross(t) => t.lerp(perc),
+      Track::SCurve(t) => t.lerp(perc),
     }
   }
 
-  fn draw(&self, ctx: &mut Window, color: Color) {
+  fn speed_limit(&self) -> Option<f32> {
     match self {
-      Track::Turn(t) => t.draw(ctx, color),
-      Track::Diag(t) => t.draw(ctx, color),
-      Track::Strt(t) => t.draw(ctx, color),
+      Track::Turn(t) => t.speed_limit(),
+      Track::Diag(t) => t.speed_limit(),
+      Track::Strt(t) => t.speed_limit(),
+      Track::Cross(t) => t.speed_limit(),
+      Track::SCurve(t) => t.speed_limit(),
     }
   }
+
+  fn tangent(&self, perc: f32) -> (f32, f32) {
+    match self {
+      Track::Turn(t) => t.tangent(perc),
+      Track::Diag(t) => t.tangent(perc),
+      Track::Strt(t) => t.tangent(perc),
+      Track::Cross(t) => t.tangent(perc),
+      Track::SCurve(t) => t.tangent(perc),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn turns_end_the_way_they_bend() {
+    let start = Connection::new(Pos(0, 1), Dir::Right);
+    assert!(Track::new(start, Connection::new(Pos(3, 2), Dir::UpRight)).is_some());
+    assert!(Track::new(start, Connection::new(Pos(3, 2), Dir::DownRight)).is_none());
+
+    // the same pieces built from the diagonal end
+    let end = Connection::new(Pos(0, 1), Dir::Left);
+    assert!(Track::new(Connection::new(Pos(3, 2), Dir::DownLeft), end).is_some());
+    assert!(Track::new(Connection::new(Pos(3, 2), Dir::UpLeft), end).is_none());
+  }
 }
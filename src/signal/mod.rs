@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+  ConnectionMap,
+  SwitchMap,
+  active_branch,
+  path::{
+    track::{Track, TrackPiece},
+    Connection,
+  },
+  train::Train,
+};
+
+/// Signals sit on a connection and face the direction of the connection,
+/// every joint with a signal (in either direction) is a block boundary
+#[derive(Default)]
+pub struct Signals {
+  signals: HashSet<Connection>,
+  // block id of every track
+  blocks: Vec<usize>,
+  // block id -> trains in that block
+  occupancy: HashMap<usize, Vec<usize>>,
+  // crossing id of every track that is part of one
+  crossings: Vec<Option<usize>>,
+  // crossing id -> trains on it
+  crossing_occupancy: HashMap<usize, Vec<usize>>,
+}
+
+impl Signals {
+  pub fn new() -> Self {
+    Signals::default()
+  }
+
+  pub fn contains(&self, conn: &Connection) -> bool {
+    self.signals.contains(conn)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item=&Connection> {
+    self.signals.iter()
+  }
+
+  pub fn insert(&mut self, conn: Connection) {
+    self.signals.insert(conn);
+  }
+
+  pub fn toggle(&mut self, conn: Connection) {
+    if !self.signals.remove(&conn) {
+      self.signals.insert(conn);
+    }
+  }
+
+  /// Drops signals whose connection doesn't lead onto a track anymore
+  pub fn retain_connected(&mut self, conns: &ConnectionMap) {
+    self.signals.retain(|conn| conns.contains_key(conn));
+  }
+
+  /// Splits the tracks into blocks, has to be called whenever tracks or signals change
+  pub fn rebuild_blocks(&mut self, tracks: &[Track], conns: &ConnectionMap) {
+    let mut parent: Vec<usize> = (0..tracks.len()).collect();
+
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+      while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+      }
+      i
+    }
+
+    for (conn, entries) in conns.iter() {
+      let opposite = Connection::new(conn.pos, conn.dir.opposite());
+
+      if self.signals.contains(conn) || self.signals.contains(&opposite) {
+        continue;
+      }
+
+      // tracks leaving this connection are joined with the ones arriving at it
+      let root = find(&mut parent, entries[0].0);
+      for (trc, _) in entries.iter().chain(conns.get(&opposite).into_iter().flatten()) {
+        let other = find(&mut parent, *trc);
+        parent[other] = root;
+      }
+    }
+
+    // a crossing and the track it crosses are a single block
+    self.crossings = vec![None; tracks.len()];
+    for (i, track) in tracks.iter().enumerate() {
+      let center = match track {
+        Track::Cross(crossing) => crossing.center(),
+        _ => continue,
+      };
+
+      self.crossings[i] = Some(i);
+      for (j, other) in tracks.iter().enumerate() {
+        if other.start().pos == center || other.end().pos == center || other.runs_through(center) {
+          self.crossings[j] = Some(i);
+
+          let (root, other) = (find(&mut parent, i), find(&mut parent, j));
+          parent[other] = root;
+        }
+      }
+    }
+
+    self.blocks = (0..tracks.len()).map(|i| find(&mut parent, i)).collect();
+  }
+
+  /// The crossing track `trc` is part of, if any
+  pub fn crossing(&self, trc: usize) -> Option<usize> {
+    self.crossings.get(trc).cloned().unwrap_or(None)
+  }
+
+  pub fn update_occupancy(&mut self, trains: &[Train]) {
+    self.occupancy.clear();
+    self.crossing_occupancy.clear();
+
+    for (i, train) in trains.iter().enumerate() {
+      for trc in train.tracks() {
+        let trains = self.occupancy.entry(self.blocks[trc]).or_default();
+        if !trains.contains(&i) {
+          trains.push(i);
+        }
+
+        if let Some(crossing) = self.crossings[trc] {
+          let trains = self.crossing_occupancy.entry(crossing).or_default();
+          if !trains.contains(&i) {
+            trains.push(i);
+          }
+        }
+      }
+    }
+  }
+
+  /// Whether a train on track `from` can go on through `conn` without running
+  /// into another train on a crossing, signal or not
+  pub fn crossing_clear(&self, conn: &Connection, from: usize, conns: &ConnectionMap, switches: &SwitchMap, train: usize) -> bool {
+    let entries = match conns.get(conn) {
+      Some(entries) => entries,
+      None => return true,
+    };
+
+    let (trc, _) = active_branch(entries, switches.get(conn));
+
+    match self.crossings[*trc] {
+      Some(crossing) if self.crossings[from] != Some(crossing) => {
+        self.crossing_occupancy
+            .get(&crossing)
+            .is_none_or(|trains| trains.iter().all(|i| *i == train))
+      }
+      _ => true,
+    }
+  }
+
+  /// Whether the block behind `conn` is free of trains, other than `train`
+  pub fn is_clear(&self, conn: &Connection, conns: &ConnectionMap, switches: &SwitchMap, train: Option<usize>) -> bool {
+    let entries = match conns.get(conn) {
+      Some(entries) => entries,
+      None => return true,
+    };
+
+    let (trc, _) = active_branch(entries, switches.get(conn));
+
+    self.occupancy
+        .get(&self.blocks[*trc])
+        .is_none_or(|trains| trains.iter().all(|i| Some(*i) == train))
+  }
+}
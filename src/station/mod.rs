@@ -0,0 +1,169 @@
+use super::path::{
+  track::{Track, TrackPiece},
+  Dir,
+  Pos,
+};
+
+// seconds trains wait at a new station
+pub const DEFAULT_DWELL: f32 = 5.;
+// dwell times a station cycles through
+const DWELL_TIMES: [f32; 5] = [2., 5., 10., 20., 40.];
+
+// names new stations get, in order
+const NAMES: [&str; 12] = [
+  "ALDER", "BIRCH", "CEDAR", "DUNE", "ELM", "FERN",
+  "GROVE", "HEATH", "IVY", "JUNIPER", "KESTREL", "LARCH",
+];
+
+/// A platform along a straight or diagonal run of track, trains that fit on it
+/// stop at its far end and wait there for `dwell` seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Station {
+  pub name: String,
+  from: Pos,
+  to: Pos,
+  pub dwell: f32,
+}
+
+impl Station {
+  /// A platform from `from` to `to`, None unless they are whole pieces apart in one direction
+  pub fn new(name: String, from: Pos, to: Pos, dwell: f32) -> Option<Self> {
+    steps(from, to)?;
+
+    Some(Station { name, from, to, dwell })
+  }
+
+  pub fn from(&self) -> Pos {
+    self.from
+  }
+
+  pub fn to(&self) -> Pos {
+    self.to
+  }
+
+  /// Direction the platform runs in from `from` to `to`
+  pub fn dir(&self) -> Dir {
+    steps(self.from, self.to).expect("stations are whole pieces long").0
+  }
+
+  /// Length of the platform in world pixels
+  pub fn len(&self) -> f32 {
+    let (dx, dy) = (self.to - self.from).to_float();
+    (dx.powi(2) + dy.powi(2)).sqrt()
+  }
+
+  /// Whether the platforms share any of their pieces
+  pub fn overlaps(&self, other: &Station) -> bool {
+    let dir = self.dir();
+    if dir != other.dir() && dir != other.dir().opposite() {
+      return false;
+    }
+
+    self.points().windows(2).any(|piece| other.contains(piece[0]) && other.contains(piece[1]))
+  }
+
+  /// Whether `pos` is one of the piece ends along the platform
+  pub fn contains(&self, pos: Pos) -> bool {
+    self.points().contains(&pos)
+  }
+
+  /// Whether every piece of the platform is on straight or diagonal track
+  pub fn on_track(&self, tracks: &[Track]) -> bool {
+    self.points().windows(2).all(|piece| {
+      tracks.iter().any(|track| self.parallel(track) && on(track, piece[0]) && on(track, piece[1]))
+    })
+  }
+
+  /// Whether the whole of `track` lies along the platform
+  pub fn covers(&self, track: &Track) -> bool {
+    self.parallel(track) && self.contains(track.start().pos) && self.contains(track.end().pos)
+  }
+
+  /// How far along `track` driven in `dir` the end of the platform is, None if a
+  /// train on that track doesn't run towards the end of the platform
+  pub fn stop(&self, track: &Track, dir: i8) -> Option<f32> {
+    if !self.parallel(track) {
+      return None;
+    }
+
+    let (start, heading) = if dir == 1 {
+      (track.start().pos, track.start().dir)
+    } else {
+      (track.end().pos, track.end().dir.opposite())
+    };
+    let end = if heading == self.dir() { self.to } else { self.from };
+
+    // anywhere else along the track is ahead of where the train enters it
+    if end == start || !on(track, end) {
+      return None;
+    }
+
+    let (dx, dy) = (end - start).to_float();
+    Some((dx.powi(2) + dy.powi(2)).sqrt())
+  }
+
+  /// Switches to the next of the preset dwell times
+  pub fn next_dwell(&mut self) {
+    self.dwell = DWELL_TIMES.iter()
+        .cloned()
+        .find(|&time| time > self.dwell)
+        .unwrap_or(DWELL_TIMES[0]);
+  }
+
+  /// Distance from `point` to the closest point of the platform, in world pixels
+  pub fn distance(&self, (x, y): (f32, f32)) -> f32 {
+    let (fx, fy) = self.from.to_float();
+    let (dx, dy) = (self.to - self.from).to_float();
+
+    let along = (((x - fx) * dx + (y - fy) * dy) / (dx.powi(2) + dy.powi(2))).clamp(0., 1.);
+    ((fx + dx * along - x).powi(2) + (fy + dy * along - y).powi(2)).sqrt()
+  }
+
+  // the ends of every piece along the platform
+  fn points(&self) -> Vec<Pos> {
+    let (dir, n) = steps(self.from, self.to).expect("stations are whole pieces long");
+    let step = dir.to_pos();
+
+    (0..n + 1).map(|i| self.from + Pos(step.0 * i, step.1 * i)).collect()
+  }
+
+  // a straight or diagonal piece running the same or the opposite way
+  fn parallel(&self, track: &Track) -> bool {
+    let dir = self.dir();
+
+    match track {
+      Track::Strt(_) | Track::Diag(_) => track.start().dir == dir || track.start().dir == dir.opposite(),
+      Track::Turn(_) | Track::SCurve(_) | Track::Cross(_) => false,
+    }
+  }
+}
+
+// whether `pos` is an end of `track` or lies in its middle
+fn on(track: &Track, pos: Pos) -> bool {
+  track.start().pos == pos || track.end().pos == pos || track.runs_through(pos)
+}
+
+// the direction and the number of pieces from `from` to `to`
+fn steps(from: Pos, to: Pos) -> Option<(Dir, i32)> {
+  let Pos(dx, dy) = to - from;
+
+  (0..8)
+      .map(|i| Dir::Up.rotate(i))
+      .filter_map(|dir| {
+        let Pos(sx, sy) = dir.to_pos();
+        let n = if sx != 0 { dx / sx } else { dy / sy };
+
+        if n > 0 && sx * n == dx && sy * n == dy { Some((dir, n)) } else { None }
+      })
+      .next()
+}
+
+/// The first of the preset names no station uses yet
+pub fn free_name(stations: &[Station]) -> String {
+  let used = |name: &str| stations.iter().any(|station| station.name == name);
+
+  match NAMES.iter().find(|name| !used(name)) {
+    Some(name) => name.to_string(),
+    None => (stations.len() + 1..).map(|i| format!("STATION {}", i)).find(|name| !used(name)).expect("there are always unused numbers"),
+  }
+}
@@ -1,21 +1,14 @@
 use std::collections::VecDeque;
+use std::fmt;
 
-use rand::{Rng, thread_rng};
-
-use quicksilver::{
-  geom::{
-    Circle
-  },
-  graphics::{Color},
-  lifecycle::{Window}
-};
+use rand::Rng;
 
 //use ggez::{
 //  Context,
 //  graphics::{
 //    self,
 //    Point2,
-////    Color,
+//    Color,
 //  },
 //  GameResult,
 //  timer::{
@@ -26,6 +19,10 @@ use quicksilver::{
 
 use super::{
   ConnectionMap,
+  SwitchMap,
+  active_branch,
+  signal::Signals,
+  station::Station,
   path::{
     track::{
       Track,
@@ -33,212 +30,646 @@ use super::{
     },
     Connection,
   },
-  draw_line
 };
 
 //type Queue = VecDeque<usize>;
 
+// distance the head keeps to a red signal or the end of a platform
+const SIGNAL_GAP: f32 = 2.;
+// most pieces a train looks ahead for speed limits and places to stop, only a safety
+// net as the search ends once it's past the braking distance
+const MAX_LOOKAHEAD_PIECES: usize = 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Colour {
+  pub r: f32,
+  pub g: f32,
+  pub b: f32,
+}
+
+/// How fast a train may go and how quickly it gets there or stops, in world
+/// pixels per second and per second squared
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Performance {
+  pub max_speed: f32,
+  pub accel: f32,
+  pub brake: f32,
+}
+
+/// The parts of the world trains drive on and look ahead along
+#[derive(Copy, Clone)]
+pub struct Network<'a> {
+  pub tracks: &'a [Track],
+  pub conns: &'a ConnectionMap,
+  pub switches: &'a SwitchMap,
+  pub signals: &'a Signals,
+  pub stations: &'a [Station],
+}
+
+/// What a spawned train is made of, lengths are in world pixels
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Consist {
+  // top speed
+  pub speed: f32,
+  pub accel: f32,
+  pub brake: f32,
+  pub cars: usize,
+  pub car_len: f32,
+  // space between two cars
+  pub gap: f32,
+}
+
+impl Consist {
+  pub fn performance(&self) -> Performance {
+    Performance {
+      max_speed: self.speed,
+      accel: self.accel,
+      brake: self.brake,
+    }
+  }
+
+  /// Length of track the whole train takes up
+  pub fn len(&self) -> f32 {
+    if self.cars == 0 {
+      return 0.;
+    }
+
+    self.cars as f32 * self.car_len + (self.cars - 1) as f32 * self.gap
+  }
+}
+
+#[derive(Debug)]
+pub enum SpawnError {
+  NoTrack,
+  NoCars,
+  // the track behind the head ends before the last car
+  NotEnoughTrack { needed: f32, found: f32 },
+}
+
+impl fmt::Display for SpawnError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SpawnError::NoTrack => write!(f, "no track to spawn on"),
+      SpawnError::NoCars => write!(f, "a train needs at least one car"),
+      SpawnError::NotEnoughTrack { needed, found } => write!(f, "train needs {} of track but only {} is free", needed, found),
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TrainState {
+  Running,
+  // waiting at a red signal
+  Waiting(Connection),
+  // ran into another train at `at`, `time` seconds ago
+  Crashed { at: (f32, f32), time: f32 },
+  // standing at a platform of `station` for `time` seconds
+  Docked { station: usize, time: f32 },
+}
+
+// what a train stops for
+#[derive(Debug, Copy, Clone)]
+enum Halt {
+  Signal(Connection),
+  Buffer,
+  Station(usize),
+}
+
 pub struct Train {
+  // tracks the head drove over, from the one under the last car up to the head's,
+  // with the direction the train drives on them
+  route: VecDeque<(usize, i8)>,
+  // how far the head is along the last track of the route
+  head_dist: f32,
+  // how far behind the head every segment is
+  offsets: Vec<f32>,
   segments: Vec<Segment>,
-  colour: Color,
+  colour: Colour,
+  state: TrainState,
+  performance: Performance,
+  speed: f32,
+  // the station the train just left, it doesn't stop there again before leaving the platform
+  served: Option<usize>,
+}
+
+/// The connection a train driving along `track` in `dir` leaves it through
+pub fn leaving(track: &Track, dir: i8) -> Connection {
+  if dir == 1 {
+    track.end()
+  } else {
+    let mut conn = track.start();
+    conn.dir = conn.dir.opposite();
+    conn
+  }
 }
 
 impl Train {
-  pub fn new(speed: f32, track: usize, dist: f32, (seg_n, seg_dist, seg_len): (usize, f32, f32), tracks: &Vec<Track>, conns: &ConnectionMap) -> Self {
+  /// Places a train with its head `dist` along `track`, driving in `dir`, and
+  /// the rest of the consist on the tracks behind it
+  pub fn new<R: Rng>(rnd: &mut R, track: usize, dist: f32, dir: i8, consist: &Consist, net: Network) -> Result<Self, SpawnError> {
+    let Network { tracks, conns, switches, .. } = net;
+
+    if consist.cars == 0 {
+      return Err(SpawnError::NoCars);
+    }
+
+    let head_track = tracks.get(track).ok_or(SpawnError::NoTrack)?;
+    let needed = consist.len();
+
+    // tracks from the head backwards with the direction the train drives on them
+    let mut route: VecDeque<(usize, i8)> = VecDeque::new();
+    route.push_front((track, dir));
+    let head_dist = if dir == 1 { dist } else { head_track.len() - dist };
+    let mut found = head_dist;
+
+    while found < needed {
+      let (trc, dir) = route[0];
+      // where a train driving backwards would leave the track
+      let back = leaving(&tracks[trc], -dir);
+
+      let (next, next_dir) = match conns.get(&back) {
+        Some(conns) => *active_branch(conns, switches.get(&back)),
+        None => return Err(SpawnError::NotEnoughTrack { needed, found }),
+      };
+
+      route.push_front((next, -next_dir));
+      found += tracks[next].len();
+    }
+
+    // front and back of every car, measured backwards from the head
+    let offsets = (0..consist.cars)
+        .flat_map(|car| {
+          let front = car as f32 * (consist.car_len + consist.gap);
+          vec![front, front + consist.car_len]
+        })
+        .collect();
+
     // random train colour
-    let mut rnd = thread_rng();
-    let colour: Color = Color {
+    let colour = Colour {
       r: rnd.gen_range(0.0, 1.0),
       g: rnd.gen_range(0.0, 1.0),
       b: rnd.gen_range(0.0, 1.0),
-      a: 1.0,
     };
 
-    // train
-    let mut segments = Vec::new();
+    // trains start out standing
+    Ok(Train::from_route(route, head_dist, offsets, colour, consist.performance(), 0., tracks))
+  }
+
+  /// Rebuilds a train from the tracks it is on, e.g. when loading a layout
+  pub fn from_route(route: VecDeque<(usize, i8)>, head_dist: f32, offsets: Vec<f32>, colour: Colour, performance: Performance, speed: f32, tracks: &[Track]) -> Self {
+    let mut train = Train {
+      route,
+      head_dist,
+      segments: offsets.iter().map(|_| Segment::new()).collect(),
+      offsets,
+      colour,
+      state: TrainState::Running,
+      performance,
+      speed,
+      served: None,
+    };
+
+    train.place(tracks);
+    for seg in train.segments.iter_mut() {
+      seg.prev_pos = seg.pos;
+    }
+
+    train
+  }
+
+  pub fn segments(&self) -> &[Segment] {
+    &self.segments
+  }
+
+  /// Tracks from the one under the last car up to the head's, with the direction
+  /// the train drives on them
+  pub fn route(&self) -> &VecDeque<(usize, i8)> {
+    &self.route
+  }
+
+  /// How far the head is along the last track of the route
+  pub fn head_dist(&self) -> f32 {
+    self.head_dist
+  }
+
+  /// How far behind the head every segment is
+  pub fn offsets(&self) -> &[f32] {
+    &self.offsets
+  }
+
+  pub fn colour(&self) -> Colour {
+    self.colour
+  }
 
-    let total_len = seg_len * seg_n as f32 + seg_dist * seg_n as f32 - 1.;
-    let mut last = dist + total_len;
+  pub fn state(&self) -> TrainState {
+    self.state
+  }
 
-    // pre calculate the queue on spawn
-    let mut head = Segment::new(speed, track, last);
-    let delta = last / speed;
-    let queue = head.update(tracks, conns, delta);
-    last -= seg_len;
+  pub fn performance(&self) -> Performance {
+    self.performance
+  }
 
-    segments.push(head);
+  pub fn speed(&self) -> f32 {
+    self.speed
+  }
 
-    for i in 0..(seg_n * 2 - 1) {
-      let mut seg = Segment::new(speed, track, last);
-      for conn in queue.iter() {
-        seg.push_conn(*conn);
+  /// Speeds up or brakes so the train keeps to the limit of every piece it is on
+  /// and can still slow down in time for the ones ahead, then moves it
+  pub fn update(&mut self, delta: f32, net: Network, id: usize) {
+    let Network { tracks, conns, switches, signals, stations } = net;
+
+    // wrecks never move again, docked trains wait out the dwell time
+    let standing = match self.state {
+      TrainState::Crashed { at, time } => Some(TrainState::Crashed { at, time: time + delta }),
+      TrainState::Docked { station, time } if time < stations[station].dwell => Some(TrainState::Docked { station, time: time + delta }),
+      TrainState::Docked { station, .. } => {
+        self.served = Some(station);
+        None
       }
-      seg.update(tracks, conns, last / speed);
-      segments.push(seg);
+      TrainState::Running | TrainState::Waiting(_) => None,
+    };
 
-      if i % 2 == 0 {
-        last -= seg_dist;
-      } else {
-        last -= seg_len;
+    if let Some(state) = standing {
+      self.state = state;
+      for seg in self.segments.iter_mut() {
+        seg.prev_pos = seg.pos;
       }
+      return;
     }
 
-    Train {
-      segments,
-      colour,
+    self.state = TrainState::Running;
+
+    let perf = self.performance;
+    // fastest the train may go right now, limits hold until the last car is past them
+    let mut allowed = self.tracks()
+        .filter_map(|trc| tracks[trc].speed_limit())
+        .fold(perf.max_speed, f32::min);
+    // the braking curve towards `limit` in `dist`, a tick early as the train moves
+    // at the new speed for a whole tick before it can brake any harder
+    let lag = perf.brake * delta;
+    let approach = |limit: f32, dist: f32| (lag.powi(2) + limit.powi(2) + 2. * perf.brake * dist.max(0.)).sqrt() - lag;
+
+    // where the train has to stop and what for
+    let mut stop = None;
+
+    {
+      // far enough to stop from top speed
+      let horizon = perf.max_speed.powi(2) / (2. * perf.brake) + SIGNAL_GAP;
+
+      // the closest platform end at least `past` along `trc` driven in `dir`, minus the gap,
+      // on platforms the whole train fits on other than the one it just left
+      let length = self.offsets.last().cloned().unwrap_or(0.);
+      let served = self.served;
+      let platform = |trc: usize, dir: i8, past: f32| stations.iter()
+          .enumerate()
+          .filter(|&(i, station)| Some(i) != served && station.len() - SIGNAL_GAP >= length)
+          .filter_map(|(i, station)| Some((i, station.stop(&tracks[trc], dir)? - SIGNAL_GAP)))
+          .filter(|&(_, along)| along >= past)
+          .min_by(|a, b| a.1.partial_cmp(&b.1).expect("distances should never be NaN"));
+
+      let (mut from, mut from_dir) = *self.route.back().expect("trains are always on some track");
+      let mut conn = leaving(&tracks[from], from_dir);
+      let mut ahead = tracks[from].len() - self.head_dist;
+
+      for _ in 0..MAX_LOOKAHEAD_PIECES {
+        // platforms end before the track does
+        let entered = ahead - tracks[from].len();
+        if let Some((i, along)) = platform(from, from_dir, -entered) {
+          stop = Some((entered + along, Halt::Station(i)));
+          break;
+        }
+
+        // only move up to the signal if the block behind it is taken, crossings
+        // are guarded the same way without one
+        let red = signals.contains(&conn) && !signals.is_clear(&conn, conns, switches, Some(id));
+        if red || !signals.crossing_clear(&conn, from, conns, switches, id) {
+          stop = Some((ahead - SIGNAL_GAP, Halt::Signal(conn)));
+          break;
+        }
+
+        let (next, dir) = match conns.get(&conn) {
+          Some(entries) => *active_branch(entries, switches.get(&conn)),
+          // buffer stop
+          None => {
+            stop = Some((ahead, Halt::Buffer));
+            break;
+          }
+        };
+
+        let track = &tracks[next];
+        if let Some(limit) = track.speed_limit() {
+          allowed = allowed.min(approach(limit, ahead));
+        }
+
+        if ahead > horizon {
+          break;
+        }
+
+        conn = leaving(track, dir);
+        ahead += track.len();
+        from = next;
+        from_dir = dir;
+      }
     }
-  }
 
-  pub fn update(&mut self, _window: &mut Window, tracks: &Vec<Track>, conns: &ConnectionMap) {
-    let delta = 1.0 / 60.0;
+    if let Some((dist, _)) = stop {
+      allowed = allowed.min(approach(0., dist));
+    }
 
-    let mut iter = self.segments.iter_mut();
+    // limits that show up closer than the brakes can manage get overrun a bit
+    self.speed = (self.speed + perf.accel * delta).min(allowed).max(self.speed - perf.brake * delta).max(0.);
+    let mut step = self.speed * delta;
+
+    // stops never get overrun though
+    let mut at_buffer = false;
+    if let Some((dist, halt)) = stop {
+      if step >= dist {
+        step = dist.max(0.);
+        self.speed = 0.;
+
+        match halt {
+          Halt::Signal(conn) => self.state = TrainState::Waiting(conn),
+          Halt::Buffer => at_buffer = true,
+          Halt::Station(station) => self.state = TrainState::Docked { station, time: 0. },
+        }
+      }
+    }
 
-    let head = iter.next().expect("Segments should always be at least 2 long, so the first element should exist");
-    let queue = head.update(tracks, conns, delta);
+    self.advance(step, tracks, conns, switches);
 
-    for seg in iter {
-      for conn in queue.iter() {
-        seg.push_conn(*conn);
+    // the station that was just served counts again once the head left its platform
+    if let Some(station) = self.served {
+      let (head, _) = *self.route.back().expect("trains are always on some track");
+      if !stations[station].covers(&tracks[head]) {
+        self.served = None;
       }
-      seg.update(tracks, conns, delta);
     }
-  }
 
-  pub fn draw(&mut self, window: &mut Window) {
     for seg in self.segments.iter_mut() {
-      seg.draw(window, self.colour);
+      seg.prev_pos = seg.pos;
     }
+    self.place(tracks);
+
+    // heads back the way it came
+    if at_buffer {
+      self.reverse(tracks);
+    }
+  }
 
-    for conn in self.segments.chunks(2) {
-      let (start, end) = (conn[0].pos, conn[1].pos);
+  // moves the head `step` on, taking the active branch at every junction
+  fn advance(&mut self, step: f32, tracks: &[Track], conns: &ConnectionMap, switches: &SwitchMap) {
+    self.head_dist += step;
 
-      draw_line(window, start.0, start.1, end.0, end.1, 10., self.colour);
+    loop {
+      let (trc, dir) = *self.route.back().expect("trains are always on some track");
+      let track = &tracks[trc];
+      let len = track.len();
+
+      if self.head_dist <= len {
+        break;
+      }
+
+      let conn = leaving(track, dir);
+      match conns.get(&conn) {
+        Some(entries) => {
+          self.route.push_back(*active_branch(entries, switches.get(&conn)));
+          self.head_dist -= len;
+        }
+        // buffer stop, the train turns around as a whole once it stands
+        None => {
+          self.head_dist = len;
+          break;
+        }
+      }
     }
+
+    self.trim(tracks);
   }
-}
 
-pub struct Segment {
-  speed: f32,
-  track: usize,
-  dist: f32,
-  pos: (f32, f32),
-  dir: i8,
-  turns: VecDeque<usize>,
-}
+  // forgets the tracks the last car has left behind
+  fn trim(&mut self, tracks: &[Track]) {
+    let tail = self.offsets.last().cloned().unwrap_or(0.);
+    // track between the head and the end of the first track of the route
+    let mut behind = self.head_dist + self.route.iter()
+        .skip(1)
+        .take(self.route.len().saturating_sub(2))
+        .map(|&(trc, _)| tracks[trc].len())
+        .sum::<f32>();
+
+    while self.route.len() > 1 && behind >= tail {
+      self.route.pop_front();
+
+      if self.route.len() > 1 {
+        behind -= tracks[self.route[0].0].len();
+      }
+    }
+  }
 
-impl Segment {
-  pub fn new(speed: f32, track: usize, dist: f32) -> Self {
-    Segment {
-      speed,
-      track,
-      dist,
-      pos: (0., 0.),
-      dir: 1,
-      turns: VecDeque::new(),
+  // where along the route a point `offset` behind the head is, as an index into the
+  // route and how far along that track in the driving direction
+  fn locate(&self, offset: f32, tracks: &[Track]) -> (usize, f32) {
+    let mut k = self.route.len() - 1;
+    let mut along = self.head_dist;
+    let mut rem = offset;
+
+    while rem > along && k > 0 {
+      rem -= along;
+      k -= 1;
+      along = tracks[self.route[k].0].len();
     }
+
+    (k, (along - rem).max(0.))
   }
 
-  pub fn push_conn(&mut self, conn: usize) {
-    self.turns.push_back(conn);
+  // puts every segment its offset behind the head
+  fn place(&mut self, tracks: &[Track]) {
+    for i in 0..self.segments.len() {
+      let (k, along) = self.locate(self.offsets[i], tracks);
+      let (trc, dir) = self.route[k];
+      let track = &tracks[trc];
+
+      let seg = &mut self.segments[i];
+      seg.track = trc;
+      seg.dir = dir;
+      seg.dist = if dir == 1 { along } else { track.len() - along };
+      seg.update_pos(track);
+    }
   }
 
-  pub fn update_track<'a>(&mut self, tracks: &'a Vec<Track>, conns: &ConnectionMap, queue: &mut VecDeque<usize>, conn: &Connection) -> Option<&'a Track> {
-    let mut rnd = thread_rng();
+  /// Moves the train to the new station indices after stations got removed or replaced
+  pub fn remap_stations(&mut self, map: &[Option<usize>]) {
+    self.served = self.served.and_then(|station| map[station]);
 
-    if let Some(conns) = conns.get(conn) {
-      let (trc, dir) = if let Some(conn) = self.turns.pop_front() {
-        conns.get(conn).expect("Connections should have the element in the queue")
-      } else {
-        let index = if conns.len() > 1 { rnd.gen_range(0, conns.len()) } else { 0 };
-        queue.push_back(index);
-        conns.get(index).expect("Connections should have at least one element")
+    if let TrainState::Docked { station, time } = self.state {
+      self.state = match map[station] {
+        Some(station) => TrainState::Docked { station, time },
+        None => TrainState::Running,
       };
+    }
+  }
 
-      let track = tracks.get(*trc).expect("Tracks in the hashmap should exist");
+  /// Stops the train for good after it ran into another one at `at`
+  pub fn crash(&mut self, at: (f32, f32)) {
+    if let TrainState::Crashed { .. } = self.state {
+      return;
+    }
 
-      if self.dir != *dir {
-        self.dir = -self.dir;
-      }
+    self.state = TrainState::Crashed { at, time: 0. };
+    self.speed = 0.;
+  }
 
-      if self.dir == -1 {
-        self.dist = track.len() - self.dist;
-      }
+  /// Turns the standing train around, the last car leads from now on
+  pub fn reverse(&mut self, tracks: &[Track]) {
+    let tail = self.offsets.last().cloned().unwrap_or(0.);
+    let (k, along) = self.locate(tail, tracks);
+
+    // the route from the last car forwards, driven the other way
+    self.route = self.route
+        .split_off(k)
+        .into_iter()
+        .rev()
+        .map(|(trc, dir)| (trc, -dir))
+        .collect();
+    self.head_dist = tracks[self.route.back().expect("trains are always on some track").0].len() - along;
+    self.offsets = self.offsets.iter().rev().map(|offset| tail - offset).collect();
+
+    // the segments stay where they are, only facing the other way
+    for seg in self.segments.iter_mut() {
+      seg.dir = -seg.dir;
+      seg.heading = (-seg.heading.0, -seg.heading.1);
+    }
 
-      self.track = *trc;
+    self.segments.reverse();
+    self.trim(tracks);
+    self.speed = 0.;
+  }
 
-      Some(track)
-    } else {
-      None
+  /// Moves the train to the new track indices after tracks got removed.
+  /// Returns false if the train was on a removed track.
+  pub fn remap_tracks(&mut self, map: &[Option<usize>]) -> bool {
+    for entry in self.route.iter_mut() {
+      entry.0 = match map[entry.0] {
+        Some(trc) => trc,
+        None => return false,
+      };
     }
-  }
 
-  pub fn use_next_track<'a>(&mut self, tracks: &'a Vec<Track>, conns: &ConnectionMap, queue: &mut VecDeque<usize>, track: &Track) -> &'a Track {
-    let len = track.len();
+    for seg in self.segments.iter_mut() {
+      seg.track = map[seg.track].expect("segments are on tracks of the route");
+    }
 
-    if self.dist > len {
-      // end of track
-      self.dist = self.dist - len;
+    true
+  }
 
-      let conn = &track.end();
+  /// Moves the train onto the halves after track `i` got cut `len` along its length,
+  /// the second half becoming track `second`. `tracks` is the network before the cut.
+  pub fn split_track(&mut self, i: usize, len: f32, second: usize, tracks: &[Track]) {
+    let last = self.route.len() - 1;
+    let mut route = VecDeque::new();
 
-      let track = match self.update_track(tracks, conns, queue, conn) {
-        Some(track) => {
-          track
-        }
-        None => {
-          self.dist = len - self.dist;
-          self.dir = -self.dir;
-          tracks.get(self.track).expect("Current track should always exist")
-        }
-      };
+    for (k, &(trc, dir)) in self.route.iter().enumerate() {
+      if trc != i {
+        route.push_back((trc, dir));
+        continue;
+      }
 
-      track
-    } else if self.dist < 0. {
-      // start of track
-      self.dist = -self.dist;
+      // the halves in the order the train drives over them, with their lengths
+      let rest = tracks[i].len() - len;
+      let (first, then) = if dir == 1 { ((i, len), (second, rest)) } else { ((second, rest), (i, len)) };
 
-      let mut conn = track.start();
-      conn.dir = conn.dir.opposite();
+      route.push_back((first.0, dir));
 
-      let track = match self.update_track(tracks, conns, queue, &conn) {
-        Some(track) => {
-          track
-        }
-        None => {
-          self.dir = -self.dir;
-          tracks.get(self.track).expect("Current track should always exist")
+      // the head may not be past the cut yet
+      if k < last || self.head_dist > first.1 {
+        route.push_back((then.0, dir));
+
+        if k == last {
+          self.head_dist -= first.1;
         }
-      };
+      }
+    }
+
+    self.route = route;
 
-      track
-    } else {
-      tracks.get(self.track).expect("Current track should always exist")
+    for seg in self.segments.iter_mut() {
+      if seg.track == i && seg.dist > len {
+        seg.track = second;
+        seg.dist -= len;
+      }
     }
   }
 
-  pub fn update(&mut self, tracks: &Vec<Track>, conns: &ConnectionMap, delta: f32) -> VecDeque<usize> {
-    let mut queue = VecDeque::new();
+  /// The stretch of every track the train covers, as distances from the start of the track
+  pub fn occupied(&self, tracks: &[Track]) -> Vec<(usize, f32, f32)> {
+    let tail = self.offsets.last().cloned().unwrap_or(0.);
+    let (first, tail_along) = self.locate(tail, tracks);
+    let last = self.route.len() - 1;
+
+    self.route.iter()
+        .enumerate()
+        .skip(first)
+        .map(|(k, &(trc, dir))| {
+          let len = tracks[trc].len();
+          let from = if k == first { tail_along } else { 0. };
+          let to = if k == last { self.head_dist } else { len };
+
+          if dir == 1 { (trc, from, to) } else { (trc, len - to, len - from) }
+        })
+        .collect()
+  }
 
-    self.dist += self.speed * delta * self.dir as f32;
+  /// Every track some part of the train is on
+  pub fn tracks<'a>(&'a self) -> impl Iterator<Item=usize> + 'a {
+    self.route.iter().map(|&(trc, _)| trc)
+  }
+}
 
-    let mut track = tracks.get(self.track).expect("tracks should have the current one");
-    let mut len = track.len();
+/// One end of a car, placed on the tracks by the train it belongs to
+pub struct Segment {
+  pub(crate) track: usize,
+  pub(crate) dist: f32,
+  pos: (f32, f32),
+  // position before the last update, for drawing in between ticks
+  prev_pos: (f32, f32),
+  // unit vector the segment is moving in
+  heading: (f32, f32),
+  pub(crate) dir: i8,
+}
 
-    while self.dist > len || self.dist < 0. {
-      track = self.use_next_track(tracks, conns, &mut queue, track);
-      len = track.len();
+impl Segment {
+  fn new() -> Self {
+    Segment {
+      track: 0,
+      dist: 0.,
+      pos: (0., 0.),
+      prev_pos: (0., 0.),
+      heading: (0., 0.),
+      dir: 1,
     }
+  }
 
-    let perc = self.dist / len;
-    let pos = track.lerp(perc);
+  pub fn pos(&self) -> (f32, f32) {
+    self.pos
+  }
+
+  /// Which way the segment moves, following the track it's on
+  pub fn heading(&self) -> (f32, f32) {
+    self.heading
+  }
 
-    self.pos = pos.to_float();
+  /// Position `alpha` of the way from the previous tick to the current one
+  pub fn lerp_pos(&self, alpha: f32) -> (f32, f32) {
+    let (px, py) = self.prev_pos;
+    let (x, y) = self.pos;
 
-    queue
+    (px + (x - px) * alpha, py + (y - py) * alpha)
   }
 
-  pub fn draw(&mut self, window: &mut Window, color: Color) {
-    window.draw(&Circle::new((self.pos.0, self.pos.1), 5), color);
+  fn update_pos(&mut self, track: &Track) {
+    let perc = self.dist / track.len();
+    let (tx, ty) = track.tangent(perc);
+
+    self.pos = track.lerp(perc);
+    self.heading = (tx * self.dir as f32, ty * self.dir as f32);
   }
 }
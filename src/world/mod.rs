@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use super::{
+  ConnectionMap,
+  SwitchMap,
+  active_branch,
+  connect_track,
+  history::Snapshot,
+  layout::{self, Layout, LayoutError},
+  path::{
+    track::{Track, TrackPiece},
+    Connection,
+    Pos,
+  },
+  signal::Signals,
+  station::{self, Station, DEFAULT_DWELL},
+  train::{Train, TrainState, Consist, Network, SpawnError},
+};
+
+// how close trains on the tracks of a crossing may get before they crash
+const CRASH_DISTANCE: f32 = 4.;
+// distance between the points trains on a crossing get sampled at
+const CRASH_SAMPLE_STEP: f32 = 2.;
+// seconds a wreck stays on the tracks before it gets cleared, if wrecks get cleared
+const WRECK_TIME: f32 = 3.;
+
+/// Two trains running into each other
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Collision {
+  // indices into `World::trains` after the update that found it
+  pub trains: (usize, usize),
+  pub pos: (f32, f32),
+}
+
+// points every few pixels between `from` and `to` along `track`
+fn samples(track: &Track, from: f32, to: f32) -> Vec<(f32, f32)> {
+  let len = track.len();
+  let steps = ((to - from) / CRASH_SAMPLE_STEP).ceil().max(1.) as i32;
+
+  (0..steps + 1)
+      .map(|i| track.lerp((from + (to - from) * i as f32 / steps as f32) / len))
+      .collect()
+}
+
+/// The whole simulation: the track network and the trains running on it.
+/// All randomness comes from one rng seeded with `seed`, so the same seed
+/// and the same edits always play out the same way.
+pub struct World {
+  pub tracks: Vec<Track>,
+  pub connections: ConnectionMap,
+  pub switches: SwitchMap,
+  pub signals: Signals,
+  pub stations: Vec<Station>,
+  pub trains: Vec<Train>,
+  // whether wrecked trains get cleared off the tracks after a while
+  pub remove_wrecks: bool,
+  seed: u64,
+  rng: ChaCha20Rng,
+}
+
+impl World {
+  pub fn new(seed: u64) -> Self {
+    World {
+      tracks: Vec::new(),
+      connections: HashMap::new(),
+      switches: HashMap::new(),
+      signals: Signals::new(),
+      stations: Vec::new(),
+      trains: Vec::new(),
+      remove_wrecks: false,
+      seed,
+      rng: ChaCha20Rng::seed_from_u64(seed),
+    }
+  }
+
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  /// Loading picks the rng up where the save left it
+  pub fn load(text: &str) -> Result<World, LayoutError> {
+    let layout = Layout::parse(text)?;
+
+    let mut world = World::new(layout.seed);
+    world.rng.set_word_pos(layout.rng_pos);
+    world.tracks = layout.tracks;
+    world.rebuild_connections();
+
+    // switches only belong where tracks branch, the same as after an edit
+    let connections = &world.connections;
+    world.switches = layout.switches;
+    world.switches.retain(|conn, _| connections.get(conn).is_some_and(|conns| conns.len() > 1));
+
+    for conn in layout.signals {
+      world.signals.insert(conn);
+    }
+    world.signals.retain_connected(&world.connections);
+    world.signals.rebuild_blocks(&world.tracks, &world.connections);
+
+    let tracks = &world.tracks;
+    world.stations = layout.stations.into_iter().filter(|station| station.on_track(tracks)).collect();
+
+    world.trains = layout.trains;
+
+    Ok(world)
+  }
+
+  pub fn save(&self) -> String {
+    layout::save(self.seed, self.rng.get_word_pos(), &self.tracks, &self.switches, self.signals.iter(), &self.stations, &self.trains)
+  }
+
+  /// Moves every train by `delta` seconds, returns the trains that crashed into each other
+  pub fn update(&mut self, delta: f32) -> Vec<Collision> {
+    // old wrecks go first so the returned collisions index the trains as they are afterwards
+    if self.remove_wrecks {
+      self.trains.retain(|train| match train.state() {
+        TrainState::Crashed { time, .. } => time < WRECK_TIME,
+        _ => true,
+      });
+    }
+
+    self.signals.update_occupancy(&self.trains);
+
+    for i in 0..self.trains.len() {
+      let net = Network {
+        tracks: &self.tracks,
+        conns: &self.connections,
+        switches: &self.switches,
+        signals: &self.signals,
+        stations: &self.stations,
+      };
+      self.trains[i].update(delta, net, i);
+      // later trains have to see the blocks this one just entered
+      self.signals.update_occupancy(&self.trains);
+    }
+
+    let collisions = self.collisions();
+    for collision in collisions.iter() {
+      let (a, b) = collision.trains;
+      self.trains[a].crash(collision.pos);
+      self.trains[b].crash(collision.pos);
+    }
+
+    collisions
+  }
+
+  // trains touching each other, except for wrecks that already crashed
+  fn collisions(&self) -> Vec<Collision> {
+    let occupied = self.trains.iter().map(|train| train.occupied(&self.tracks)).collect::<Vec<_>>();
+    let mut collisions = Vec::new();
+
+    for a in 0..self.trains.len() {
+      for b in a + 1..self.trains.len() {
+        if let (TrainState::Crashed { .. }, TrainState::Crashed { .. }) = (self.trains[a].state(), self.trains[b].state()) {
+          continue;
+        }
+
+        if let Some(pos) = self.touching(&occupied[a], &occupied[b]) {
+          collisions.push(Collision { trains: (a, b), pos });
+        }
+      }
+    }
+
+    collisions
+  }
+
+  // where two trains covering the given stretches of track touch, either on the
+  // same track or where the tracks of a crossing cross
+  fn touching(&self, a: &[(usize, f32, f32)], b: &[(usize, f32, f32)]) -> Option<(f32, f32)> {
+    for &(ta, a_from, a_to) in a {
+      for &(tb, b_from, b_to) in b {
+        let track = &self.tracks[ta];
+
+        if ta == tb {
+          if a_from <= b_to && b_from <= a_to {
+            return Some(track.lerp((a_from.max(b_from) + a_to.min(b_to)) / 2. / track.len()));
+          }
+          continue;
+        }
+
+        let crossing = self.signals.crossing(ta);
+        if crossing.is_none() || crossing != self.signals.crossing(tb) {
+          continue;
+        }
+
+        let other = samples(&self.tracks[tb], b_from, b_to);
+        for (x, y) in samples(track, a_from, a_to) {
+          if let Some(&(ox, oy)) = other.iter().find(|(ox, oy)| (ox - x).powi(2) + (oy - y).powi(2) < CRASH_DISTANCE.powi(2)) {
+            return Some(((x + ox) / 2., (y + oy) / 2.));
+          }
+        }
+      }
+    }
+
+    None
+  }
+
+  /// Spawns a train with its head `dist` along `track` heading in `dir`,
+  /// refused if the cars behind it don't fit on the track
+  pub fn spawn_train(&mut self, track: usize, dist: f32, dir: i8, consist: &Consist) -> Result<(), SpawnError> {
+    let net = Network {
+      tracks: &self.tracks,
+      conns: &self.connections,
+      switches: &self.switches,
+      signals: &self.signals,
+      stations: &self.stations,
+    };
+    let train = Train::new(&mut self.rng, track, dist, dir, consist, net)?;
+    self.trains.push(train);
+
+    Ok(())
+  }
+
+  /// The track closest to `pos` within `radius` and how far along it that point is
+  pub fn track_at(&self, pos: (f32, f32), radius: f32) -> Option<(usize, f32)> {
+    self.tracks.iter()
+        .enumerate()
+        .map(|(i, track)| (i, track.closest(pos)))
+        .filter(|(_, (_, dist))| *dist < radius)
+        .min_by(|a, b| (a.1).1.partial_cmp(&(b.1).1).expect("distances should never be NaN"))
+        .map(|(i, (along, _))| (i, along))
+  }
+
+  /// Connections where a train has more than one track to choose from
+  pub fn junctions<'a>(&'a self) -> impl Iterator<Item=(&'a Connection, &'a Vec<(usize, i8)>)> + 'a {
+    self.connections.iter().filter(|(_, conns)| conns.len() > 1)
+  }
+
+  /// Connections new track can leave `pos` through to join existing track, with
+  /// whether nothing is connected on that side yet
+  pub fn exits_at(&self, pos: Pos) -> Vec<(Connection, bool)> {
+    let mut exits = self.connections
+        .keys()
+        .filter(|conn| conn.pos == pos)
+        .map(|conn| {
+          // trains heading into the existing track come from the other side
+          let exit = Connection::new(pos, conn.dir.opposite());
+          (exit, !self.connections.contains_key(&exit))
+        })
+        .collect::<Vec<(Connection, bool)>>();
+
+    // the middle of a straight run can be left either way
+    for track in self.tracks.iter().filter(|track| track.runs_through(pos)) {
+      let dir = track.start().dir;
+      exits.push((Connection::new(pos, dir), false));
+      exits.push((Connection::new(pos, dir.opposite()), false));
+    }
+
+    exits.sort();
+    exits
+  }
+
+  /// Cycles the junction to its next branch
+  pub fn toggle_switch(&mut self, conn: Connection) {
+    let conns = match self.connections.get(&conn) {
+      Some(conns) => conns,
+      None => return,
+    };
+
+    let current = active_branch(conns, self.switches.get(&conn)).0;
+    let index = conns.iter().position(|(i, _)| *i == current).expect("the active branch is part of the junction");
+
+    self.switches.insert(conn, conns[(index + 1) % conns.len()].0);
+  }
+
+  /// Places or removes a signal for trains passing the connection
+  pub fn toggle_signal(&mut self, conn: Connection) {
+    self.signals.toggle(conn);
+    self.signals.rebuild_blocks(&self.tracks, &self.connections);
+  }
+
+  /// Whether a platform from `from` to `to` runs along straight or diagonal track
+  /// without overlapping another one
+  pub fn station_allowed(&self, from: Pos, to: Pos) -> bool {
+    match Station::new(String::new(), from, to, DEFAULT_DWELL) {
+      Some(station) => station.on_track(&self.tracks) && !self.stations.iter().any(|other| other.overlaps(&station)),
+      None => false,
+    }
+  }
+
+  /// Places a station with a platform from `from` to `to`, false if it isn't allowed there
+  pub fn add_station(&mut self, from: Pos, to: Pos) -> bool {
+    if !self.station_allowed(from, to) {
+      return false;
+    }
+
+    let station = Station::new(station::free_name(&self.stations), from, to, DEFAULT_DWELL).expect("allowed platforms are straight");
+    self.stations.push(station);
+
+    true
+  }
+
+  pub fn remove_station(&mut self, i: usize) {
+    let mut stations = self.stations.clone();
+    stations.remove(i);
+    self.replace_stations(stations);
+  }
+
+  /// The station with its platform closest to `pos` within `radius`
+  pub fn station_at(&self, pos: (f32, f32), radius: f32) -> Option<usize> {
+    self.stations.iter()
+        .enumerate()
+        .map(|(i, station)| (i, station.distance(pos)))
+        .filter(|&(_, dist)| dist < radius)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).expect("distances should never be NaN"))
+        .map(|(i, _)| i)
+  }
+
+  /// The train standing at the platform of station `i`
+  pub fn docked_at(&self, i: usize) -> Option<&Train> {
+    self.trains.iter().find(|train| match train.state() {
+      TrainState::Docked { station, .. } => station == i,
+      _ => false,
+    })
+  }
+
+  /// Adds new pieces, straight runs they start or end in the middle of get split
+  /// so the pieces join them as a junction
+  pub fn add_tracks(&mut self, pieces: Vec<Track>) {
+    for track in pieces.iter() {
+      self.split_at(track.start().pos);
+      self.split_at(track.end().pos);
+
+      // only the part of a crossed run right next to the crossing gets locked with it
+      if let Track::Cross(crossing) = track {
+        let center = crossing.center();
+        let joints = self.tracks.iter().flat_map(|other| other.joints_around(center)).collect::<Vec<Pos>>();
+
+        for joint in joints {
+          self.split_at(joint);
+        }
+      }
+    }
+
+    for track in pieces {
+      let i = self.tracks.len();
+      connect_track(&mut self.connections, i, &track);
+      self.tracks.push(track)
+    }
+
+    self.signals.rebuild_blocks(&self.tracks, &self.connections);
+  }
+
+  /// Cuts the straight run going through `pos` in two, false if there is none
+  pub fn split_at(&mut self, pos: Pos) -> bool {
+    let split = self.tracks.iter().enumerate().filter_map(|(i, track)| Some((i, track.split(pos)?))).next();
+
+    let (i, (first, second)) = match split {
+      Some(split) => split,
+      None => return false,
+    };
+
+    let len = first.len();
+    let new = self.tracks.len();
+
+    for train in self.trains.iter_mut() {
+      train.split_track(i, len, new, &self.tracks);
+    }
+
+    // a junction entering the run from its end now enters the second half
+    let end = self.tracks[i].end();
+    for (conn, trc) in self.switches.iter_mut() {
+      if *trc == i && conn.pos == end.pos {
+        *trc = new;
+      }
+    }
+
+    self.tracks[i] = first;
+    self.tracks.push(second);
+
+    self.rebuild_connections();
+    self.signals.rebuild_blocks(&self.tracks, &self.connections);
+
+    true
+  }
+
+  pub fn remove_tracks(&mut self, removed: &[usize]) {
+    if removed.is_empty() {
+      return;
+    }
+
+    // old index -> new index, None for removed tracks
+    let mut map = Vec::with_capacity(self.tracks.len());
+    let mut tracks = Vec::with_capacity(self.tracks.len());
+
+    for (i, track) in self.tracks.drain(..).enumerate() {
+      if removed.contains(&i) {
+        map.push(None);
+      } else {
+        map.push(Some(tracks.len()));
+        tracks.push(track);
+      }
+    }
+
+    // switches keep their state unless the active branch is gone
+    let switches = std::mem::take(&mut self.switches);
+    for (conn, trc) in switches {
+      if let Some(trc) = map[trc] {
+        self.switches.insert(conn, trc);
+      }
+    }
+
+    self.replace_tracks(tracks, &map);
+  }
+
+  pub fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      tracks: self.tracks.clone(),
+      switches: self.switches.clone(),
+      signals: self.signals.iter().cloned().collect(),
+      stations: self.stations.clone(),
+    }
+  }
+
+  pub fn restore(&mut self, snapshot: Snapshot) {
+    // trains stay on tracks that exist in both versions
+    let mut used = vec![false; snapshot.tracks.len()];
+    let map = self.tracks
+        .iter()
+        .map(|track| {
+          let i = snapshot.tracks.iter().enumerate().position(|(i, other)| !used[i] && other == track)?;
+          used[i] = true;
+          Some(i)
+        })
+        .collect::<Vec<Option<usize>>>();
+
+    self.switches = snapshot.switches;
+
+    self.signals = Signals::new();
+    for conn in snapshot.signals {
+      self.signals.insert(conn);
+    }
+
+    self.replace_stations(snapshot.stations);
+    self.replace_tracks(snapshot.tracks, &map);
+  }
+
+  fn rebuild_connections(&mut self) {
+    self.connections.clear();
+    for (i, track) in self.tracks.iter().enumerate() {
+      connect_track(&mut self.connections, i, track);
+    }
+  }
+
+  /// Swaps in a new set of tracks, `map` takes the old track indices to the new ones
+  fn replace_tracks(&mut self, tracks: Vec<Track>, map: &[Option<usize>]) {
+    self.tracks = tracks;
+    self.rebuild_connections();
+
+    let connections = &self.connections;
+    self.switches.retain(|conn, _| connections.get(conn).is_some_and(|conns| conns.len() > 1));
+
+    self.signals.retain_connected(&self.connections);
+    self.signals.rebuild_blocks(&self.tracks, &self.connections);
+
+    // trains that were on a removed piece get despawned
+    let trains = std::mem::take(&mut self.trains);
+    self.trains = trains
+        .into_iter()
+        .filter_map(|mut train| if train.remap_tracks(map) { Some(train) } else { None })
+        .collect();
+
+    // platforms need the track under them
+    let tracks = &self.tracks;
+    let stations = self.stations.iter().filter(|station| station.on_track(tracks)).cloned().collect();
+    self.replace_stations(stations);
+  }
+
+  /// Swaps in a new set of stations, trains keep to the ones with the same platform
+  fn replace_stations(&mut self, stations: Vec<Station>) {
+    let map = self.stations
+        .iter()
+        .map(|old| stations.iter().position(|new| (new.from(), new.to()) == (old.from(), old.to())))
+        .collect::<Vec<Option<usize>>>();
+
+    for train in self.trains.iter_mut() {
+      train.remap_stations(&map);
+    }
+
+    self.stations = stations;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use path::{obstacles::Obstacles, Dir, Path};
+
+  const TICK: f32 = 1. / 60.;
+
+  fn consist(cars: usize) -> Consist {
+    Consist { speed: 80., accel: 40., brake: 40., cars, car_len: 20., gap: 5. }
+  }
+
+  // lays a run of track from `from` heading `dir` to `to`, crossing what's in the way
+  fn lay(world: &mut World, from: Pos, dir: Dir, to: Pos) {
+    let mut path = Path::new(from, dir);
+    path.set_obstacles(Obstacles::new(&world.tracks));
+    path.add_path(to);
+
+    world.add_tracks(path.into_pieces().expect("there is room for the track"));
+  }
+
+  // a world with a single run of track from the left edge to `to`
+  fn world(seed: u64, to: Pos) -> World {
+    let mut world = World::new(seed);
+    lay(&mut world, Pos(0, 1), Dir::Right, to);
+    world
+  }
+
+  fn crashed(train: &Train) -> bool {
+    matches!(train.state(), TrainState::Crashed { .. })
+  }
+
+  // runs the world until trains collide
+  fn run_to_collision(world: &mut World) -> Collision {
+    for _ in 0..60 * 30 {
+      if let Some(&collision) = world.update(TICK).first() {
+        return collision;
+      }
+    }
+    panic!("the trains never met");
+  }
+
+  #[test]
+  fn save_and_load_round_trip() {
+    let mut world = world(3, Pos(20, 21));
+    let last = world.tracks.len() - 1;
+    world.spawn_train(last, 10., 1, &consist(3)).expect("the track is long enough");
+    world.add_station(Pos(0, 1), Pos(4, 1));
+    for _ in 0..120 {
+      world.update(TICK);
+    }
+
+    let text = world.save();
+    let loaded = World::load(&text).expect("saved layouts load");
+
+    assert_eq!(loaded.tracks, world.tracks);
+    assert_eq!(loaded.stations, world.stations);
+    assert_eq!(loaded.trains.len(), 1);
+    assert_eq!(loaded.save(), text);
+  }
+
+  #[test]
+  fn loading_carries_on_the_rng() {
+    let mut world = world(5, Pos(40, 1));
+    world.spawn_train(2, 10., 1, &consist(1)).expect("the track is long enough");
+    let mut loaded = World::load(&world.save()).expect("saved layouts load");
+
+    world.spawn_train(6, 10., 1, &consist(1)).expect("the track is long enough");
+    loaded.spawn_train(6, 10., 1, &consist(1)).expect("the track is long enough");
+
+    assert_eq!(loaded.trains[1].colour(), world.trains[1].colour());
+    assert_ne!(loaded.trains[1].colour(), loaded.trains[0].colour());
+  }
+
+  #[test]
+  fn same_seed_and_edits_give_the_same_world() {
+    let run = || {
+      let mut world = world(7, Pos(40, 1));
+      let last = world.tracks.len() - 1;
+      world.spawn_train(last, 10., 1, &consist(2)).expect("the track is long enough");
+      for _ in 0..300 {
+        world.update(TICK);
+      }
+      let signal = world.tracks[2].end();
+      world.toggle_signal(signal);
+      for _ in 0..300 {
+        world.update(TICK);
+      }
+      world.save()
+    };
+
+    assert_eq!(run(), run());
+  }
+
+  #[test]
+  fn spawn_needs_room_for_every_car() {
+    let mut world = world(1, Pos(4, 1));
+
+    match world.spawn_train(0, 30., 1, &consist(4)) {
+      Err(SpawnError::NotEnoughTrack { needed, found }) => assert!(found < needed),
+      _ => panic!("a four car train doesn't fit on a single piece"),
+    }
+    assert!(world.trains.is_empty());
+  }
+
+  #[test]
+  fn trains_turn_back_at_buffer_stops() {
+    let mut world = world(1, Pos(30, 1));
+    let last = world.tracks.len() - 1;
+    world.spawn_train(last, 5., 1, &consist(1)).expect("the track is long enough");
+
+    let heading = |world: &World| world.trains[0].route().back().expect("trains are on a track").1;
+    let start = heading(&world);
+
+    let mut ticks = 0;
+    while heading(&world) == start {
+      world.update(TICK);
+      ticks += 1;
+      assert!(ticks < 60 * 30, "the train never turned back");
+    }
+
+    if let TrainState::Crashed { .. } = world.trains[0].state() {
+      panic!("the train ran into the buffer");
+    }
+
+    let (x, _) = world.trains[0].segments()[0].pos();
+    for _ in 0..120 {
+      world.update(TICK);
+    }
+    assert!(world.trains[0].segments()[0].pos().0 < x, "the train should be heading back to the left");
+  }
+
+  #[test]
+  fn trains_meeting_head_on_crash() {
+    let mut world = world(1, Pos(40, 1));
+    let last = world.tracks.len() - 1;
+    world.spawn_train(2, 10., 1, &consist(1)).expect("the track is long enough");
+    world.spawn_train(last - 2, 10., -1, &consist(1)).expect("the track is long enough");
+
+    let collision = run_to_collision(&mut world);
+
+    assert_eq!(collision.trains, (0, 1));
+    assert!(crashed(&world.trains[0]) && crashed(&world.trains[1]));
+  }
+
+  #[test]
+  fn trains_crash_on_a_crossing() {
+    let mut world = world(1, Pos(40, 1));
+    lay(&mut world, Pos(21, -20), Dir::Up, Pos(21, 20));
+    // both lines run through the middle of the crossing, a train stands on each
+    let centre = Pos(21, 1).to_float();
+    let on_crossing = world.tracks.iter()
+        .enumerate()
+        .map(|(i, track)| (i, track.closest(centre)))
+        .filter(|&(_, (_, dist))| dist < 1.)
+        .map(|(i, (along, _))| (i, along))
+        .collect::<Vec<_>>();
+    assert_eq!(on_crossing.len(), 2);
+
+    for &(trc, along) in on_crossing.iter() {
+      world.spawn_train(trc, along + 10., 1, &consist(1)).expect("the track is long enough");
+    }
+
+    let collision = run_to_collision(&mut world);
+
+    assert_eq!(collision.trains, (0, 1));
+    assert!(crashed(&world.trains[0]) && crashed(&world.trains[1]));
+  }
+
+  #[test]
+  fn collisions_name_the_trains_left_after_wrecks_are_cleared() {
+    let mut world = world(1, Pos(40, 1));
+    world.remove_wrecks = true;
+    // a pair that meets right away, and one far apart on a line of its own
+    let first = world.tracks.len();
+    world.spawn_train(2, 10., 1, &consist(1)).expect("the track is long enough");
+    world.spawn_train(5, 10., -1, &consist(1)).expect("the track is long enough");
+    lay(&mut world, Pos(0, 41), Dir::Right, Pos(80, 41));
+    let last = world.tracks.len() - 1;
+    world.spawn_train(first + 1, 10., 1, &consist(1)).expect("the track is long enough");
+    world.spawn_train(last - 1, 10., -1, &consist(1)).expect("the track is long enough");
+
+    assert_eq!(run_to_collision(&mut world).trains, (0, 1));
+    let collision = run_to_collision(&mut world);
+
+    // the first wrecks are gone by the time the second pair meets
+    assert_eq!(world.trains.len(), 2);
+    assert_eq!(collision.trains, (0, 1));
+    assert!(crashed(&world.trains[0]) && crashed(&world.trains[1]));
+    assert!(collision.pos.1 > Pos(0, 40).to_float().1);
+  }
+}